@@ -1,7 +1,8 @@
 //! ゲーム全体の描画命令をレンダーに送る.
 
 use crate::gameplay::{
-    block::block_datas::{self, BlockType}, field, game_manager::{GameManager, GameState, PlayStyle, TitleChoice}, game_renderer_sender::GameRendererSender, gameplay_manager::GameplayManager
+    block::{block_datas::BlockType, next_blocks::RandomizerType, piece_set::PieceSet, rotation_system::RotationSystemType}, field::{FieldConfig, GravityType}, game_rules::GameRulesType, game_manager::{FieldSize, GameManager, GameState, OptionsChoice, PlayStyle, ReplaySpeed, TitleChoice}, game_renderer_sender::GameRendererSender, gameplay_manager::GameplayManager, targeting::TargetingType, visibility::VisibilityType,
+    clear_event::ClearEvent, t_spin_checker::TSpinType, handling_settings::{ARR_INSTANT, SOFT_DROP_INFINITE},
 };
 use crate::utility::grid::Grid;
 use crate::console_renderer::render_manager::RenderQueueData;
@@ -9,7 +10,16 @@ use crate::RENDER_MANAGER;
 use crossterm::style::Color;
 use std::collections::VecDeque;
 
-const GAMEPLAY_WIDTH: i32 = 60;
+/// フィールドの左端の位置.
+const FIELD_POS_X: i32 = 20;
+/// フィールドの右端から次のブロックの表示までの間隔.
+const NEXT_BLOCKS_MARGIN_X: i32 = 7;
+/// 次のブロックの表示の左端から、隣のプレイ画面までの幅.
+const NEXT_BLOCKS_AREA_WIDTH: i32 = 17;
+/// 消し方の表示が消えるまでの時間.
+const CALLOUT_MILLIS: u128 = 1000;
+/// 消し方の表示に使う行数.
+const CALLOUT_ROWS: usize = 4;
 
 pub struct GameSender {
 }
@@ -23,13 +33,14 @@ impl GameSender {
     fn make_playing_queues(&self, game: &GameManager) -> VecDeque<RenderQueueData> {
         let mut queues = VecDeque::new();
         for (index, gameplay) in game.gameplay_managers.iter().enumerate() {
-            let gameplay_sender = GamePlaySender::new(Grid::new(10 + GAMEPLAY_WIDTH * index as i32, 0));
+            let gameplay_width = calc_next_blocks_pos_x(gameplay.get_field_config()) + NEXT_BLOCKS_AREA_WIDTH;
+            let gameplay_sender = GamePlaySender::new(Grid::new(10 + gameplay_width * index as i32, 0));
             queues.append(&mut gameplay_sender.gameplay_sender(gameplay));
         }
         queues
     }
 
-    fn calc_center_pos_to_left_pos(&self, x: i32, str: &String) -> i32{
+    fn calc_center_pos_to_left_pos(&self, x: i32, str: &str) -> i32{
         let str_width: i32 = str.chars().map(|char| if char.is_ascii() { 1 } else { 2 } ).sum();
         let result = x - str_width / 2;
         if result < 0 {0} else {result}
@@ -51,17 +62,106 @@ impl GameRendererSender for GameSender {
                             PlayStyle::VSPlayer => " 1人でプレイ  NPCとプレイ -2人でプレイ-",
                         }
                     },
-                    TitleChoice::Exit => " 1人でプレイ  NPCとプレイ  2人でプレイ ",
+                    _ => " 1人でプレイ  NPCとプレイ  2人でプレイ ",
+                });
+                let field_size_str = {
+                    let field_size_name = match game.get_field_size() {
+                        FieldSize::Standard => "標準",
+                        FieldSize::Wide => "ワイド",
+                        FieldSize::Narrow => "細い",
+                        FieldSize::Tall => "高い",
+                    };
+                    let config = game.get_field_size().to_config();
+                    let field_size_str = format!("フィールド：{} {}x{}", field_size_name, config.width, config.visible_height);
+                    match game.get_title_choice_command() {
+                        TitleChoice::FieldSize => format!("-{}-", field_size_str),
+                        _ => field_size_str,
+                    }
+                };
+                let rotation_system_str = {
+                    let rotation_system_str = format!("回転法則：{}", match game.get_rotation_system_type() {
+                        RotationSystemType::Srs => "SRS風",
+                        RotationSystemType::Classic => "補正なし",
+                        RotationSystemType::Ars => "ARS風",
+                    });
+                    match game.get_title_choice_command() {
+                        TitleChoice::RotationSystem => format!("-{}-", rotation_system_str),
+                        _ => rotation_system_str,
+                    }
+                };
+                let piece_set_str = {
+                    let piece_set_str = format!("ブロック：{}", game.get_piece_set().get_name());
+                    match game.get_title_choice_command() {
+                        TitleChoice::PieceSet => format!("-{}-", piece_set_str),
+                        _ => piece_set_str,
+                    }
+                };
+                let randomizer_str = {
+                    let randomizer_str = format!("ブロックの出方：{}", match game.get_randomizer_type() {
+                        RandomizerType::Bag => "全種類1巡",
+                        RandomizerType::DoubleBag => "全種類2巡",
+                        RandomizerType::Random => "完全ランダム",
+                        RandomizerType::History => "連続しにくい",
+                    });
+                    match game.get_title_choice_command() {
+                        TitleChoice::Randomizer => format!("-{}-", randomizer_str),
+                        _ => randomizer_str,
+                    }
+                };
+                let rules_str = {
+                    let rules_str = format!("ルール：{}", match game.get_rules_type() {
+                        GameRulesType::Standard => "標準",
+                        GameRulesType::ModernFast => "速い",
+                        GameRulesType::ClassicSlow => "ゆっくり",
+                        GameRulesType::Practice => "練習",
+                    });
+                    match game.get_title_choice_command() {
+                        TitleChoice::Rules => format!("-{}-", rules_str),
+                        _ => rules_str,
+                    }
+                };
+                let big_str = {
+                    let big_str = format!("ビッグ：{}", if game.is_big_mode() {"あり"} else {"なし"});
+                    match game.get_title_choice_command() {
+                        TitleChoice::Big => format!("-{}-", big_str),
+                        _ => big_str,
+                    }
+                };
+                let visibility_str = {
+                    let visibility_str = format!("スタック表示：{}", match game.get_visibility_type() {
+                        VisibilityType::Normal => "通常",
+                        VisibilityType::Fading => "消えていく",
+                        VisibilityType::Invisible => "見えない",
+                    });
+                    match game.get_title_choice_command() {
+                        TitleChoice::Visibility => format!("-{}-", visibility_str),
+                        _ => visibility_str,
+                    }
+                };
+                let gravity_str = {
+                    let gravity_str = format!("重力：{}", match game.get_gravity_type() {
+                        GravityType::Naive => "なし",
+                        GravityType::Sticky => "塊",
+                        GravityType::Cascade => "1マスずつ",
+                    });
+                    match game.get_title_choice_command() {
+                        TitleChoice::Gravity => format!("-{}-", gravity_str),
+                        _ => gravity_str,
+                    }
+                };
+                let options_str = String::from(match game.get_title_choice_command() {
+                    TitleChoice::Options => "-操作設定-",
+                    _ => "操作設定",
                 });
                 let exit_str = String::from(match game.get_title_choice_command() {
-                    TitleChoice::Play => "やめる",
                     TitleChoice::Exit => "-やめる-",
+                    _ => "やめる",
                 });
                 let high_score_str = format!("現在のハイスコア：{:>10}", game.get_high_score());
                 // TODO: コマンドをkey_code_to_console_key_codeなどから取得して表示する.
                 let tutorial_str = String::from("操作：↑↓→←キー　決定：Enter");
-                let gameplay_tutorial_str = String::from("1Pブロック操作：wasd 回転：zx ホールド:c ドロップ：f ポーズ：r");
-                let vs_tutorial_str = String::from("2Pブロック操作：ijkl 回転：,m ホールド:. ドロップ：; ポーズ：p");
+                let gameplay_tutorial_str = String::from("1Pブロック操作：wasd 回転：zx 180度回転：v ホールド:c ドロップ：f ポーズ：r 攻撃先：q");
+                let vs_tutorial_str = String::from("2Pブロック操作：ijkl 回転：,m 180度回転：n ホールド:. ドロップ：; ポーズ：p 攻撃先：u");
 
                 queues.push_back(RenderQueueData::new(Grid::new(self.calc_center_pos_to_left_pos(title_center_pos_x, &title_str), 8), 
                                             title_str, Color::White));
@@ -69,15 +169,73 @@ impl GameRendererSender for GameSender {
                                             high_score_str, Color::White));
                 queues.push_back(RenderQueueData::new(Grid::new(self.calc_center_pos_to_left_pos(title_center_pos_x, &start_str), 13), 
                                             start_str, Color::White));
-                queues.push_back(RenderQueueData::new(Grid::new(self.calc_center_pos_to_left_pos(title_center_pos_x, &exit_str), 14), 
+                queues.push_back(RenderQueueData::new(Grid::new(self.calc_center_pos_to_left_pos(title_center_pos_x, &field_size_str), 14), 
+                                            field_size_str, Color::White));
+                queues.push_back(RenderQueueData::new(Grid::new(self.calc_center_pos_to_left_pos(title_center_pos_x, &rotation_system_str), 15), 
+                                            rotation_system_str, Color::White));
+                queues.push_back(RenderQueueData::new(Grid::new(self.calc_center_pos_to_left_pos(title_center_pos_x, &piece_set_str), 16), 
+                                            piece_set_str, Color::White));
+                queues.push_back(RenderQueueData::new(Grid::new(self.calc_center_pos_to_left_pos(title_center_pos_x, &randomizer_str), 17), 
+                                            randomizer_str, Color::White));
+                queues.push_back(RenderQueueData::new(Grid::new(self.calc_center_pos_to_left_pos(title_center_pos_x, &rules_str), 18), 
+                                            rules_str, Color::White));
+                queues.push_back(RenderQueueData::new(Grid::new(self.calc_center_pos_to_left_pos(title_center_pos_x, &big_str), 19), 
+                                            big_str, Color::White));
+                queues.push_back(RenderQueueData::new(Grid::new(self.calc_center_pos_to_left_pos(title_center_pos_x, &visibility_str), 20), 
+                                            visibility_str, Color::White));
+                queues.push_back(RenderQueueData::new(Grid::new(self.calc_center_pos_to_left_pos(title_center_pos_x, &gravity_str), 21), 
+                                            gravity_str, Color::White));
+                queues.push_back(RenderQueueData::new(Grid::new(self.calc_center_pos_to_left_pos(title_center_pos_x, &options_str), 22), 
+                                            options_str, Color::White));
+                queues.push_back(RenderQueueData::new(Grid::new(self.calc_center_pos_to_left_pos(title_center_pos_x, &exit_str), 23), 
                                             exit_str, Color::White));
-                queues.push_back(RenderQueueData::new(Grid::new(self.calc_center_pos_to_left_pos(title_center_pos_x, &tutorial_str), 16),
+                queues.push_back(RenderQueueData::new(Grid::new(self.calc_center_pos_to_left_pos(title_center_pos_x, &tutorial_str), 25),
                                             tutorial_str, Color::White));
-                queues.push_back(RenderQueueData::new(Grid::new(self.calc_center_pos_to_left_pos(title_center_pos_x, &gameplay_tutorial_str), 17),
+                queues.push_back(RenderQueueData::new(Grid::new(self.calc_center_pos_to_left_pos(title_center_pos_x, &gameplay_tutorial_str), 26),
                                             gameplay_tutorial_str, Color::White));
-                queues.push_back(RenderQueueData::new(Grid::new(self.calc_center_pos_to_left_pos(title_center_pos_x, &vs_tutorial_str), 18),
+                queues.push_back(RenderQueueData::new(Grid::new(self.calc_center_pos_to_left_pos(title_center_pos_x, &vs_tutorial_str), 27),
                                             vs_tutorial_str, Color::White));
             },
+            GameState::Options => {
+                let options_center_pos_x = 20;
+                let profile = game.get_options_profile();
+                let handling = &profile.handling;
+                let title_str = String::from("操作設定");
+                let rows = [
+                    (OptionsChoice::Player, format!("プレイヤー：{}", profile.name)),
+                    (OptionsChoice::Das, format!("リピート開始(DAS)：{}ms", handling.das_ms)),
+                    (OptionsChoice::Arr, if handling.arr_ms == ARR_INSTANT {
+                            String::from("リピート間隔(ARR)：瞬間")
+                        }
+                        else {
+                            format!("リピート間隔(ARR)：{}ms", handling.arr_ms)
+                        }),
+                    (OptionsChoice::SoftDropFactor, if handling.soft_drop_factor == SOFT_DROP_INFINITE {
+                            String::from("ソフトドロップ：一番下まで")
+                        }
+                        else {
+                            format!("ソフトドロップ：{}倍", handling.soft_drop_factor)
+                        }),
+                    (OptionsChoice::DasCut, format!("リピート停止(DASカット)：{}ms", handling.das_cut_ms)),
+                    (OptionsChoice::KeepDasCharge, format!("リピートの持ち越し：{}", if handling.keep_das_charge {"する"} else {"しない"})),
+                    (OptionsChoice::Back, String::from("保存して戻る")),
+                ];
+                queues.push_back(RenderQueueData::new(Grid::new(self.calc_center_pos_to_left_pos(options_center_pos_x, &title_str), 8), 
+                                            title_str, Color::White));
+                for (index, (choice, row_str)) in rows.into_iter().enumerate() {
+                    let row_str = if *game.get_options_choice() == choice {format!("-{}-", row_str)} else {row_str};
+                    queues.push_back(RenderQueueData::new(Grid::new(self.calc_center_pos_to_left_pos(options_center_pos_x, &row_str), 11 + index as i32), 
+                                                row_str, Color::White));
+                }
+                if let Some(message) = game.get_options_message() {
+                    let message_str = format!("保存できませんでした：{}", message);
+                    queues.push_back(RenderQueueData::new(Grid::new(self.calc_center_pos_to_left_pos(options_center_pos_x, &message_str), 19), 
+                                                message_str, Color::Red));
+                }
+                let tutorial_str = String::from("選択：↑↓キー　変更：→←キー　決定：Enter");
+                queues.push_back(RenderQueueData::new(Grid::new(self.calc_center_pos_to_left_pos(options_center_pos_x, &tutorial_str), 21),
+                                            tutorial_str, Color::White));
+            },
             GameState::Playing => {
                 queues.append(&mut self.make_playing_queues(game));
             },
@@ -98,6 +256,28 @@ impl GameRendererSender for GameSender {
                 }
                 queues.push_back(RenderQueueData::new(Grid::new(33,12), String::from(" 　press Enter　 "), Color::White));
                 queues.push_back(RenderQueueData::new(Grid::new(33,13), String::from("　　　　　　　　　"), Color::White));
+                queues.push_back(RenderQueueData::new(Grid::new(33,14), String::from("　　　シード　　　"), Color::White));
+                queues.push_back(RenderQueueData::new(Grid::new(33,15), format!("{:^18}", game.get_seed()), Color::White));
+                queues.push_back(RenderQueueData::new(Grid::new(33,16), String::from("　　　　　　　　　"), Color::White));
+                if let Some(message) = game.get_replay_message() {
+                    queues.push_back(RenderQueueData::new(Grid::new(33,17), message.to_string(), Color::White));
+                }
+            },
+            GameState::Replay => {
+                queues.append(&mut self.make_playing_queues(game));
+                let finished = game.gameplay_managers.iter().all(|gm| gm.is_game_over());
+                let status_str = if finished {
+                    String::from("リプレイ終了　決定：Enter")
+                }
+                else {
+                    let speed_str = match game.get_replay_speed() {
+                        ReplaySpeed::Normal => "等速",
+                        ReplaySpeed::Fast => "早送り",
+                        ReplaySpeed::Step => "コマ送り",
+                    };
+                    format!("リプレイ　速度：{}　変更：→←キー　コマ送り：Enter　終了：r", speed_str)
+                };
+                queues.push_back(RenderQueueData::new(Grid::new(0, 0), status_str, Color::White));
             },
         };
         let mut render_manager = RENDER_MANAGER.lock().unwrap();
@@ -116,7 +296,8 @@ impl GamePlaySender {
         }
     }
 
-    fn make_cells_queues(&self, cells: &[Vec<BlockType>], window_width: usize, window_height: usize, start_pos: &Grid, force_color: Option<Color>) -> Result<VecDeque<RenderQueueData>, &'static str> {
+    /// セルを枠付きで描画する.
+    fn make_cells_queues(&self, cells: &[Vec<BlockType>], window_width: usize, window_height: usize, start_pos: &Grid, piece_set: &PieceSet, force_color: Option<Color>) -> Result<VecDeque<RenderQueueData>, &'static str> {
         let mut queues = VecDeque::new();
         if window_height < cells.len() || window_width < cells[0].len() {
             // エラー.枠サイズがおかしい.
@@ -124,7 +305,7 @@ impl GamePlaySender {
         }
         let base_color = if let Some(color) = force_color {color} else {Color::White};
         let mut write_height = 0;
-        let header_height = (window_height - cells.len() + 1) / 2;
+        let header_height = (window_height - cells.len()).div_ceil(2);
         let mut header_string = String::from("┏");
         for _ in 0..window_width {
             header_string += "━━";
@@ -154,15 +335,15 @@ impl GamePlaySender {
             render_string = String::from("┃");
             next_position_x += 1;
             if use_harf_buffer {
-                render_string.push_str(" ");
+                render_string.push(' ');
                 next_position_x += 1;
             }
             for _ in 0..oneside_buffer_width {
-                render_string.push_str("　");
+                render_string.push('　');
                 next_position_x += 2;
             }
             for block_cell in cells_line.iter() {
-                let cell_color = if let Some(color) = force_color {color} else {get_block_color(*block_cell)};
+                let cell_color = if let Some(color) = force_color {color} else {get_block_color(piece_set, *block_cell)};
                 if render_color != cell_color {
                     queues.push_back(RenderQueueData::new(Grid::new(render_position_x, write_height) + start_pos, render_string, render_color));
                     render_string = String::from("");
@@ -184,10 +365,10 @@ impl GamePlaySender {
                 render_position_x = next_position_x;
             }
             for _ in 0..oneside_buffer_width {
-                render_string.push_str("　");
+                render_string.push('　');
             }
             if use_harf_buffer {
-                render_string.push_str(" ");
+                render_string.push(' ');
             }
             render_string += "┃";
             queues.push_back(RenderQueueData::new(Grid::new(render_position_x, write_height) + start_pos, render_string, render_color));
@@ -214,11 +395,11 @@ impl GamePlaySender {
         Ok(queues)
     }
 
-    fn make_block_queues(&self, block_type: BlockType, start_pos: &Grid) -> VecDeque<RenderQueueData>{
-        let block = block_datas::block_shape(block_type);
+    fn make_block_queues(&self, block_type: BlockType, start_pos: &Grid, piece_set: &PieceSet) -> VecDeque<RenderQueueData>{
+        let block = piece_set.shape(block_type);
         let window_width = 5;
         let window_height = 5;
-        self.make_cells_queues(&block, window_width, window_height, start_pos, None).expect("ブロック書き込みに失敗")
+        self.make_cells_queues(&block, window_width, window_height, start_pos, piece_set, None).expect("ブロック書き込みに失敗")
     }
 
     fn make_raw_block_queues(&self, block: &Vec<Vec<BlockType>>, color: Color, start_pos_left_bottom: &Grid) -> VecDeque<RenderQueueData>{
@@ -238,9 +419,11 @@ impl GamePlaySender {
 
     pub fn gameplay_sender(&self, gameplay: &GameplayManager) ->VecDeque<RenderQueueData> {
         let mut queues = VecDeque:: new();
+        let piece_set = gameplay.get_piece_set();
+        let now = gameplay.get_now();
         // ホールドブロックの表示
         let hold_pos = Grid::new(7, 1) + &self.pos;
-        queues.append(&mut self.make_block_queues(gameplay.get_hold_block(), &hold_pos));
+        queues.append(&mut self.make_block_queues(gameplay.get_hold_block(), &hold_pos, piece_set));
 
         // スコアとステータスの表示.
         let score_pos_x = 1;
@@ -264,48 +447,162 @@ impl GamePlaySender {
             score_pos_y += 1;
             let render_string = format!("COMBOS:    {: >8}", stats.combos);
             queues.push_back(RenderQueueData::new(Grid::new(score_pos_x, score_pos_y) + &self.pos, render_string, Color::White));
+            score_pos_y += 1;
+            let render_string = format!("MAX_COMBO: {: >8}", stats.max_combos);
+            queues.push_back(RenderQueueData::new(Grid::new(score_pos_x, score_pos_y) + &self.pos, render_string, Color::White));
+            score_pos_y += 1;
+            let render_string = format!("MAX_CHAIN: {: >8}", stats.max_chains);
+            queues.push_back(RenderQueueData::new(Grid::new(score_pos_x, score_pos_y) + &self.pos, render_string, Color::White));
+            score_pos_y += 1;
+            let render_string = format!("B2B:       {: >8}", stats.back_to_backs);
+            queues.push_back(RenderQueueData::new(Grid::new(score_pos_x, score_pos_y) + &self.pos, render_string, Color::White));
+            score_pos_y += 1;
+            let render_string = format!("PERFECT:   {: >8}", stats.perfect_clears);
+            queues.push_back(RenderQueueData::new(Grid::new(score_pos_x, score_pos_y) + &self.pos, render_string, Color::White));
+            score_pos_y += 1;
+            // 直前の消し方を少しの間表示する.時間が経つと薄くなって消える.
+            let callout = gameplay.get_last_clear_event()
+                .and_then(|(event, time)| calc_callout_color(now.duration_since(time).as_millis()).map(|color| (make_callout_lines(event), color)));
+            let (callout_lines, callout_color) = callout.unwrap_or((vec![], Color::White));
+            for i in 0..CALLOUT_ROWS {
+                let render_string = format!("{: >19}", callout_lines.get(i).map_or("", |line| line.as_str()));
+                queues.push_back(RenderQueueData::new(Grid::new(score_pos_x, score_pos_y) + &self.pos, render_string, callout_color));
+                score_pos_y += 1;
+            }
+            let pending_garbage = gameplay.get_pending_garbage();
+            let render_string = format!("GARBAGE:   {: >8}", pending_garbage);
+            queues.push_back(RenderQueueData::new(Grid::new(score_pos_x, score_pos_y) + &self.pos, render_string, if pending_garbage > 0 {Color::Red} else {Color::White}));
+            score_pos_y += 1;
+            let render_string = format!("BADGES:    {: >8}", gameplay.get_badges());
+            queues.push_back(RenderQueueData::new(Grid::new(score_pos_x, score_pos_y) + &self.pos, render_string, Color::White));
+            score_pos_y += 2;
+            let targeting_name = match gameplay.get_targeting_type() {
+                TargetingType::All => "ALL",
+                TargetingType::Random => "RANDOM",
+                TargetingType::Attackers => "ATTACKERS",
+                TargetingType::Badges => "BADGES",
+                TargetingType::KO => "KO",
+            };
+            let render_string = format!("TARGET:   {: >9}", targeting_name);
+            queues.push_back(RenderQueueData::new(Grid::new(score_pos_x, score_pos_y) + &self.pos, render_string, Color::White));
+            score_pos_y += 1;
+            // 狙っている相手は画面の左から順に#1,#2...と表示する.
+            let targets: Vec<String> = gameplay.get_targets().iter().map(|target| format!("#{}", target + 1)).collect();
+            let render_string = format!("{: >19}", targets.join(" "));
+            queues.push_back(RenderQueueData::new(Grid::new(score_pos_x, score_pos_y) + &self.pos, render_string, Color::White));
         }
 
         // フィールドの表示.
         // コントロールブロック描画のために、フィールドの上部を少し空けておく.
-        let field_pos = Grid::new(20, 3) + &self.pos;
+        let field_config = gameplay.get_field_config();
+        let buffer_height = field_config.buffer_height as i32;
+        let field_pos = Grid::new(FIELD_POS_X, 3) + &self.pos;
         let field_pos_except_frame = Grid::new(field_pos.x + 1, field_pos.y + 1);
-        queues.append(&mut self.make_cells_queues(&gameplay.get_field_data()[block_datas::BLOCK_START_POSITION_Y as usize..], field::FIELD_WIDTH, field::FIELD_HEIGHT_WITH_OUTSIDE - block_datas::BLOCK_START_POSITION_Y as usize
-                            , &field_pos, if gameplay.is_game_over() {Some(Color::Grey)} else {None}).expect("フィールド書き込みに失敗"));
+        // 見えないモードなどでは、表示するマスをゲーム側の見せ方に問い合わせて、見えないマスは空にしておく.
+        let visible_cells: Vec<Vec<BlockType>> = gameplay.get_field_data()[field_config.buffer_height..].iter().enumerate()
+            .map(|(y, line)| line.iter().enumerate()
+                .map(|(x, cell)| if gameplay.is_cell_visible(&Grid::new(x as i32, (y + field_config.buffer_height) as i32), now) {*cell} else {BlockType::None})
+                .collect())
+            .collect();
+        queues.append(&mut self.make_cells_queues(&visible_cells, field_config.width, field_config.visible_height
+                            , &field_pos, piece_set, if gameplay.is_game_over() {Some(Color::Grey)} else {None}).expect("フィールド書き込みに失敗"));
 
         // 影の表示.
         let mut ghost_pos = gameplay.get_ghost_pos();
         ghost_pos.x *= 2;
-        ghost_pos.y -= block_datas::BLOCK_START_POSITION_Y;
+        ghost_pos.y -= buffer_height;
         ghost_pos = ghost_pos + &field_pos_except_frame;
         queues.append(&mut self.make_raw_block_queues(&gameplay.get_control_block().block, Color::Grey, &ghost_pos));
 
         // コントロールブロックの表示.
         let mut control_block_pos = gameplay.get_control_block().position.clone();
         control_block_pos.x *= 2;
-        control_block_pos.y -= block_datas::BLOCK_START_POSITION_Y;
+        control_block_pos.y -= buffer_height;
         control_block_pos = control_block_pos + &field_pos_except_frame;
-        queues.append(&mut self.make_raw_block_queues(&gameplay.get_control_block().block, get_block_color(gameplay.get_control_block().block_type), &control_block_pos));
+        queues.append(&mut self.make_raw_block_queues(&gameplay.get_control_block().block, get_block_color(piece_set, gameplay.get_control_block().block_type), &control_block_pos));
 
         // 次のブロックの表示.
-        let mut next_blocks_pos = Grid::new(43, 0) + &self.pos;
+        let mut next_blocks_pos = Grid::new(calc_next_blocks_pos_x(field_config), 0) + &self.pos;
         let next_block_margin = Grid::new(0, 8);
         let disp_next_block_count = 3;
         for i in 0..disp_next_block_count {
-            queues.append(&mut self.make_block_queues(gameplay.get_next_block(i), &next_blocks_pos));
+            queues.append(&mut self.make_block_queues(gameplay.get_next_block(i), &next_blocks_pos, piece_set));
             next_blocks_pos = next_blocks_pos + &next_block_margin;
         }
         queues
     }
 }
 
-fn get_block_color(block_type: BlockType) -> Color {
+/// 消し方の表示名.
+fn clear_event_name(event: &ClearEvent) -> String {
+    let lines_name = match event.lines {
+        0 => String::new(),
+        1 => String::from("SINGLE"),
+        2 => String::from("DOUBLE"),
+        3 => String::from("TRIPLE"),
+        4 => String::from("QUAD"),
+        lines => format!("{} LINES", lines),
+    };
+    let t_spin_name = match (event.spin_block, event.t_spin) {
+        (_, TSpinType::None) => String::new(),
+        (_, TSpinType::Full) => String::from("T-SPIN"),
+        (_, TSpinType::Mini) => String::from("T-SPIN MINI"),
+        (BlockType::Other(_), TSpinType::Other) => String::from("SPIN"),
+        (block_type, TSpinType::Other) => format!("{:?}-SPIN", block_type),
+    };
+    let chain_name = if event.chain > 0 {format!("{} CHAIN", event.chain + 1)} else {String::new()};
+    [t_spin_name.as_str(), lines_name.as_str(), chain_name.as_str()].iter()
+        .filter(|name| !name.is_empty())
+        .copied()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// 消し方の表示内容.消し方、バックトゥバック、コンボ、全消しの順に1行ずつ.
+fn make_callout_lines(event: &ClearEvent) -> Vec<String> {
+    let mut lines = vec![clear_event_name(event)];
+    if event.back_to_back {
+        lines.push(String::from("BACK-TO-BACK"));
+    }
+    // 2回目の連続消去から1コンボと数える.
+    if event.combo >= 2 {
+        lines.push(format!("{} COMBO", event.combo - 1));
+    }
+    if event.perfect_clear {
+        lines.push(String::from("ALL CLEAR"));
+    }
+    lines
+}
+
+/// 消し方を表示してからの経過時間に応じた色.表示し終わっていたらNone.
+fn calc_callout_color(elapsed_millis: u128) -> Option<Color> {
+    if elapsed_millis < CALLOUT_MILLIS / 2 {
+        Some(Color::White)
+    }
+    else if elapsed_millis < CALLOUT_MILLIS * 4 / 5 {
+        Some(Color::Grey)
+    }
+    else if elapsed_millis < CALLOUT_MILLIS {
+        Some(Color::DarkGrey)
+    }
+    else {
+        None
+    }
+}
+
+/// 次のブロックを表示する横位置.フィールドの幅に応じて右にずらす.
+fn calc_next_blocks_pos_x(field_config: &FieldConfig) -> i32 {
+    FIELD_POS_X + field_config.width as i32 * 2 + 2 + NEXT_BLOCKS_MARGIN_X
+}
+
+/// ブロックの色.ブロックセットに定義された色を使う.
+fn get_block_color(piece_set: &PieceSet, block_type: BlockType) -> Color {
     match block_type {
-        BlockType::I => Color::Cyan,
-        BlockType::J => Color::Blue,
-        BlockType::L => Color::Magenta,
-        BlockType::T => Color::Rgb { r: (255), g: (0), b: (255) },
         BlockType::Attacked => Color::Rgb { r: {128}, g: {128}, b: {128}},
         BlockType::None => Color::White,
+        _ => match piece_set.color(block_type) {
+            Some(color) => Color::Rgb { r: color.r, g: color.g, b: color.b },
+            None => Color::White,
+        },
     }
 }
@@ -1,48 +1,26 @@
 //! ブロックのデータ定義と関連した関数.
 
-use crate::utility::grid::Grid;
-use crate::utility::vector_util;
-
-/// ブロックを操作可能にする場合に、配置される場所.
-pub const BLOCK_START_POSITION_Y: i32 = 17;
-pub const BLOCK_START_POSITION: Grid = Grid { x: 2, y: BLOCK_START_POSITION_Y };
-
 /// ブロックの種類.
+/// 標準的な名前のブロックはそれぞれの値になり、それ以外はブロックセット内の番号で区別する.
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum BlockType {
-    I, L, J, T, Attacked, None
+    I, L, J, T, O, S, Z, Other(u8), Attacked, None
 }
 
-const BLOCK_I: [[BlockType; 3]; 3] = [
-    [BlockType::None, BlockType::None, BlockType::None],
-    [BlockType::I, BlockType::I, BlockType::I],
-    [BlockType::None, BlockType::None, BlockType::None],
-];
-const BLOCK_J: [[BlockType; 3]; 3] = [
-    [BlockType::None, BlockType::J, BlockType::None],
-    [BlockType::None, BlockType::J, BlockType::J],
-    [BlockType::None, BlockType::None, BlockType::None],
-];
-const BLOCK_L: [[BlockType; 3]; 3] = [
-    [BlockType::None, BlockType::L, BlockType::None],
-    [BlockType::L, BlockType::L, BlockType::None],
-    [BlockType::None, BlockType::None, BlockType::None],
-];
-const BLOCK_T: [[BlockType; 3]; 3] = [
-    [BlockType::None, BlockType::T, BlockType::None],
-    [BlockType::T, BlockType::T, BlockType::T],
-    [BlockType::None, BlockType::None, BlockType::None],
-];
-
-/// 種類に応じたブロックの初期形.
-pub fn block_shape(block_type: BlockType) -> Vec<Vec<BlockType>> {
-    match block_type {
-        BlockType::I => vector_util::array_to_vec_2d(BLOCK_I),
-        BlockType::T => vector_util::array_to_vec_2d(BLOCK_T),
-        BlockType::J => vector_util::array_to_vec_2d(BLOCK_J),
-        BlockType::L => vector_util::array_to_vec_2d(BLOCK_L),
-        BlockType::Attacked => vec!(vec!(BlockType::Attacked)),
-        BlockType::None => vec!(vec!(BlockType::None)),
+impl BlockType {
+    /// ブロック定義ファイルのidからブロックの種類を決める.
+    /// 標準的な名前でなければ、other_indexを使ってBlockType::Otherにする.
+    pub fn from_id(id: &str, other_index: u8) -> Self {
+        match id {
+            "I" => BlockType::I,
+            "L" => BlockType::L,
+            "J" => BlockType::J,
+            "T" => BlockType::T,
+            "O" => BlockType::O,
+            "S" => BlockType::S,
+            "Z" => BlockType::Z,
+            _ => BlockType::Other(other_index),
+        }
     }
 }
 
@@ -58,21 +36,45 @@ pub fn calc_block_bottom(block: &Vec<Vec<BlockType>>) -> usize {
     0
 }
 
+/// ブロックの形を拡大する.1マスがscale x scaleマスになる.
+pub fn scale_block(block: &Vec<Vec<BlockType>>, scale: usize) -> Vec<Vec<BlockType>> {
+    block.iter().flat_map(|line| {
+        let scaled_line: Vec<BlockType> = line.iter().flat_map(|cell| std::iter::repeat_n(*cell, scale)).collect();
+        std::iter::repeat_n(scaled_line, scale)
+    }).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn test_block_shape() {
-        let t_shape = block_shape(BlockType::T);
-        assert_eq!(t_shape.len(), 3);
-        assert_eq!(t_shape[0].len(), 3);
-        assert_eq!(t_shape[1][0], BlockType::T);
-        assert_eq!(t_shape[1][1], BlockType::T);
-        assert_eq!(t_shape[1][2], BlockType::T);
+    fn test_from_id() {
+        assert_eq!(BlockType::from_id("T", 0), BlockType::T);
+        assert_eq!(BlockType::from_id("Z", 0), BlockType::Z);
+        assert_eq!(BlockType::from_id("F5", 3), BlockType::Other(3));
     }
     #[test]
     fn test_calc_block_bottom() {
-        assert_eq!(calc_block_bottom(&block_shape(BlockType::I)), 1);
+        let block = vec![
+            vec![BlockType::None, BlockType::None, BlockType::None],
+            vec![BlockType::I, BlockType::I, BlockType::I],
+            vec![BlockType::None, BlockType::None, BlockType::None],
+        ];
+        assert_eq!(calc_block_bottom(&block), 1);
+    }
+    #[test]
+    fn test_scale_block() {
+        let block = vec![
+            vec![BlockType::O, BlockType::None],
+            vec![BlockType::None, BlockType::O],
+        ];
+        assert_eq!(scale_block(&block, 1), block);
+        assert_eq!(scale_block(&block, 2), vec![
+            vec![BlockType::O, BlockType::O, BlockType::None, BlockType::None],
+            vec![BlockType::O, BlockType::O, BlockType::None, BlockType::None],
+            vec![BlockType::None, BlockType::None, BlockType::O, BlockType::O],
+            vec![BlockType::None, BlockType::None, BlockType::O, BlockType::O],
+        ]);
     }
 }
\ No newline at end of file
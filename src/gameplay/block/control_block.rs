@@ -1,26 +1,61 @@
 //! 操作できるブロックのための処理.
 
 use crate::utility::grid::Grid;
-use crate::gameplay::block::block_datas::{self, BlockType};
+use crate::gameplay::block::{
+    block_datas::{self, BlockType},
+    piece_set::PieceSet,
+    rotation_system::{Orientation, RotationSystem},
+};
 use crate::utility::vector_util;
 use crate::gameplay::field::Field;
+use std::sync::Arc;
+
+/// 最後に成功した回転で使った補正.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RotationKick {
+    // 補正表の番号.補正なしで回転できた場合は0.
+    pub index: usize,
+    // ずらした量.拡大率の単位.
+    pub offset: Grid,
+    // 180度回転ならtrue.
+    pub half_turn: bool,
+}
 
 /// 操作されるブロックの構造体.
-#[derive(PartialEq, Clone)]
+#[derive(Clone)]
 pub struct ControlBlock {
     // 現在操作中のブロックの位置.一番左下の座標とする.
     pub position: Grid,
     pub block: Vec<Vec<BlockType>>,
     pub block_type: BlockType,
+    pub orientation: Orientation,
+    rotation_system: Arc<dyn RotationSystem + Send + Sync>,
+    piece_set: Arc<PieceSet>,
+    // ブロックの拡大率.移動もこの単位で行う.
+    scale: usize,
+    // スコア計算用に、ソフトドロップとハードドロップで落ちたマス数を数えておく.
+    soft_drop_cells: u32,
+    hard_drop_cells: u32,
+    // 最後に成功した操作が回転なら、その時に使った補正.移動するとNoneに戻る.
+    last_rotation_kick: Option<RotationKick>,
 }
 
 impl ControlBlock {
-    /// 新規インスタンス作成.
-    pub fn new() -> Self {
+    /// 新規インスタンス作成.回転の補正は指定された回転法則に従う.
+    /// ブロックの形はブロックセットから取得する.
+    /// scaleが2以上なら1マスがscale x scaleマスになり、移動や回転の補正もscaleマス単位になる.
+    pub fn new(rotation_system: Arc<dyn RotationSystem + Send + Sync>, piece_set: Arc<PieceSet>, scale: usize) -> Self {
         ControlBlock {
             position: Grid::new(0, 0),
             block: vec![],
             block_type: BlockType::None,
+            orientation: Orientation::Spawn,
+            rotation_system,
+            piece_set,
+            scale,
+            soft_drop_cells: 0,
+            hard_drop_cells: 0,
+            last_rotation_kick: None,
         }
     
     }
@@ -28,11 +63,15 @@ impl ControlBlock {
     /// ブロックの割り当て.
     pub fn apply_block(&mut self, block_type: BlockType, start_position: Grid) {
         self.block_type = block_type;
-        self.block = block_datas::block_shape(block_type);
-        self.position = start_position;
+        self.block = block_datas::scale_block(&self.piece_set.shape(block_type), self.scale);
+        self.orientation = Orientation::Spawn;
+        self.position = start_position + &self.scaled(self.piece_set.spawn_offset(block_type));
         // ブロック下部の空白は先に埋めてしまう.
         let padding = self.block.len() - 1 - block_datas::calc_block_bottom(&self.block);
         self.position.y += padding as i32;
+        self.soft_drop_cells = 0;
+        self.hard_drop_cells = 0;
+        self.last_rotation_kick = None;
     }
 
     /// 割り当てブロックの削除.
@@ -40,12 +79,64 @@ impl ControlBlock {
         self.position = Grid::new(0, 0);
         self.block = vec![];
         self.block_type =  BlockType::None;
+        self.orientation = Orientation::Spawn;
+        self.soft_drop_cells = 0;
+        self.hard_drop_cells = 0;
+        self.last_rotation_kick = None;
+    }
+
+    /// 拡大率の単位での移動量を、フィールドのマス単位にする.
+    fn scaled(&self, offset: Grid) -> Grid {
+        Grid::new(offset.x * self.scale as i32, offset.y * self.scale as i32)
+    }
+
+    /// 拡大していない状態のブロックの形を返す.
+    pub fn unit_block(&self) -> Vec<Vec<BlockType>> {
+        self.block.iter().step_by(self.scale)
+            .map(|line| line.iter().step_by(self.scale).copied().collect())
+            .collect()
+    }
+
+    /// 拡大率を返す.
+    pub fn get_scale(&self) -> usize {
+        self.scale
+    }
+
+    /// 最後に成功した操作が回転なら、使った補正表の番号を返す.補正なしで回転できた場合は0.
+    /// 回転の後に移動していればNone.
+    pub fn get_last_rotation_kick(&self) -> Option<usize> {
+        self.last_rotation_kick.as_ref().map(|kick| kick.index)
+    }
+
+    /// 最後に成功した操作が回転なら、使った補正の内容を返す.回転の後に移動していればNone.
+    pub fn get_last_rotation(&self) -> Option<&RotationKick> {
+        self.last_rotation_kick.as_ref()
+    }
+
+    /// 今の位置からoffset(拡大率の単位)だけずらした位置にぶつからずに置けるかどうか.
+    fn can_move(&self, field: &Field, offset: Grid) -> bool {
+        self.block_type != BlockType::None && !field.check_collision(&self.block, &(self.position.clone() + &self.scaled(offset)))
+    }
+
+    /// 左右にも上にも動かせない状態かどうか.スピンの判定に使う.
+    pub fn is_immobile(&self, field: &Field) -> bool {
+        !self.can_move(field, Grid::new(-1, 0)) && !self.can_move(field, Grid::new(1, 0)) && !self.can_move(field, Grid::new(0, -1))
     }
 
     /// 下移動.移動出来ない場合は何もしない.
     pub fn down(&mut self, field: &Field)  -> bool {
-        if self.block_type != BlockType::None && !field.check_collision(&self.block, &Grid::new(self.position.x, self.position.y + 1)) {
-            self.position.y += 1;
+        if self.can_move(field, Grid::new(0, 1)) {
+            self.position.y += self.scale as i32;
+            self.last_rotation_kick = None;
+            return true;
+        }
+        false
+    }
+
+    /// ソフトドロップ.下移動と同じだが、落ちたマス数を数える.
+    pub fn soft_drop(&mut self, field: &Field) -> bool {
+        if self.down(field) {
+            self.soft_drop_cells += 1;
             return true;
         }
         false
@@ -53,16 +144,29 @@ impl ControlBlock {
 
     /// 最下部まで落とす.
     pub fn hard_drop(&mut self, field: &Field) -> bool {
-        while self.block_type != BlockType::None && !field.check_collision(&self.block, &Grid::new(self.position.x, self.position.y + 1)) {
-            self.position.y += 1;
+        while self.can_move(field, Grid::new(0, 1)) {
+            self.position.y += self.scale as i32;
+            self.hard_drop_cells += 1;
+            self.last_rotation_kick = None;
         }
         true
     }
 
+    /// ソフトドロップで落ちたマス数を返す.
+    pub fn get_soft_drop_cells(&self) -> u32 {
+        self.soft_drop_cells
+    }
+
+    /// ハードドロップで落ちたマス数を返す.
+    pub fn get_hard_drop_cells(&self) -> u32 {
+        self.hard_drop_cells
+    }
+
     /// 左移動.移動出来ない場合は何もしない.
     pub fn left(&mut self, field: &Field) -> bool {
-        if self.block_type != BlockType::None && !field.check_collision(&self.block, &Grid::new(self.position.x -1 , self.position.y)) {
-            self.position.x -= 1;
+        if self.can_move(field, Grid::new(-1, 0)) {
+            self.position.x -= self.scale as i32;
+            self.last_rotation_kick = None;
             return true;
         }
         false
@@ -70,51 +174,59 @@ impl ControlBlock {
 
     /// 右移動.移動出来ない場合は何もしない.
     pub fn right(&mut self, field: &Field) -> bool {
-        if self.block_type != BlockType::None && !field.check_collision(&self.block, &Grid::new(self.position.x + 1, self.position.y)) {
-            self.position.x += 1;
+        if self.can_move(field, Grid::new(1, 0)) {
+            self.position.x += self.scale as i32;
+            self.last_rotation_kick = None;
             return true;
         }
         false
     }
 
     /// 右回転.回転出来ない場合は何もしない.
-    /// 単に回転するのではなく、障害物に応じて多少移動する.
+    /// 単に回転するのではなく、回転法則の補正表に従って多少移動する.
     pub fn rotate(&mut self, field: &Field) -> bool {
         if self.block_type == BlockType::None {
             return false;
         }
         let rotated = vector_util::rotate_vec_90_clockwise(&self.block);
-        let compensation = match self.block_type {
-            BlockType::I =>  [Grid::new(0, 0), Grid::new(-2, 0), Grid::new(1, 0), Grid::new(-2, 1), Grid::new(-1, -2)],
-            _ => [Grid::new(0, 0), Grid::new(-1, 0), Grid::new(-1, -1), Grid::new(0, 2), Grid::new(-1, 2)],
-        };
-        for offset in compensation.iter() {
-            let test_position = Grid::new(self.position.x + offset.x, self.position.y + offset.y);
-            if !field.check_collision(&rotated, &test_position) {
-                self.position = test_position;
-                self.block = rotated;
-                return true;
-            }
-        }
-        false
+        self.try_rotate(field, rotated, self.orientation.clockwise())
     }
 
     /// 左回転.回転出来ない場合は何もしない.
-    /// 単に回転するのではなく、障害物に応じて多少移動する.
+    /// 単に回転するのではなく、回転法則の補正表に従って多少移動する.
     pub fn counter_rotate(&mut self, field: &Field) -> bool {
         if self.block_type == BlockType::None {
             return false;
         }
         let rotated = vector_util::rotate_vec_90_counterclockwise(&self.block);
-        let compensation = match self.block_type {
-            BlockType::I =>  [Grid::new(0, 0), Grid::new(-1, 0), Grid::new(2, 0), Grid::new(-1, -2), Grid::new(2, 1)],
-            _ => [Grid::new(0, 0), Grid::new(1, 0), Grid::new(1, -1), Grid::new(0, 2), Grid::new(1, 2)],
-        };
-        for offset in compensation.iter() {
-            let test_position = Grid::new(self.position.x + offset.x, self.position.y + offset.y);
+        self.try_rotate(field, rotated, self.orientation.counter_clockwise())
+    }
+
+    /// 180度回転.回転出来ない場合は何もしない.
+    /// 単に回転するのではなく、回転法則の180度回転用の補正表に従って多少移動する.
+    pub fn rotate_180(&mut self, field: &Field) -> bool {
+        if self.block_type == BlockType::None {
+            return false;
+        }
+        let rotated = vector_util::rotate_vec_90_clockwise(&vector_util::rotate_vec_90_clockwise(&self.block));
+        self.try_rotate(field, rotated, self.orientation.opposite())
+    }
+
+    /// 補正表の位置を順番に試して、最初にぶつからなかった位置で回転させる.
+    /// 使った補正表の番号を覚えておく.
+    fn try_rotate(&mut self, field: &Field, rotated: Vec<Vec<BlockType>>, to: Orientation) -> bool {
+        let kicks = self.rotation_system.kicks(self.block_type, self.orientation, to);
+        for (kick_index, offset) in kicks.iter().enumerate() {
+            let test_position = self.position.clone() + &self.scaled(offset.clone());
             if !field.check_collision(&rotated, &test_position) {
                 self.position = test_position;
                 self.block = rotated;
+                self.last_rotation_kick = Some(RotationKick {
+                    index: kick_index,
+                    offset: offset.clone(),
+                    half_turn: to == self.orientation.opposite(),
+                });
+                self.orientation = to;
                 return true;
             }
         }
@@ -125,13 +237,18 @@ impl ControlBlock {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::gameplay::field;
+    use crate::gameplay::field::FieldConfig;
+    use crate::gameplay::block::rotation_system::RotationSystemType;
+
+    fn piece_set() -> Arc<PieceSet> {
+        Arc::new(PieceSet::builtin().remove(0))
+    }
 
     #[test]
     fn test_rotate() {
-        let mut control_block = ControlBlock::new();
-        control_block.apply_block(BlockType::T, block_datas::BLOCK_START_POSITION);
-        let field = Field::new();
+        let mut control_block = ControlBlock::new(RotationSystemType::Srs.create(), piece_set(), 1);
+        let field = Field::new(FieldConfig::standard());
+        control_block.apply_block(BlockType::T, field.get_config().spawn_position());
         control_block.rotate(&field);
         assert_eq!(control_block.block, vec![
             vec![BlockType::None, BlockType::T, BlockType::None],
@@ -149,9 +266,9 @@ mod tests {
 
     #[test]
     fn test_movement() {
-        let mut control_block = ControlBlock::new();
-        control_block.apply_block(BlockType::I, block_datas::BLOCK_START_POSITION);
-        let field = Field::new();
+        let mut control_block = ControlBlock::new(RotationSystemType::Srs.create(), piece_set(), 1);
+        let field = Field::new(FieldConfig::standard());
+        control_block.apply_block(BlockType::I, field.get_config().spawn_position());
         // ブロックの左下が必ずしもフィールドの一番下に来るわけではないので調整が必要.
         let mut block_position_adjust = 0; 
         'outer: for y in (0..control_block.block.len()).rev() {
@@ -173,6 +290,89 @@ mod tests {
         assert_eq!(control_block.position, Grid::new(2, 18 + block_position_adjust));
 
         control_block.hard_drop(&field);
-        assert_eq!(control_block.position.y, field::FIELD_HEIGHT_WITH_OUTSIDE as i32 - 1 + block_position_adjust);
+        assert_eq!(control_block.position.y, field.get_config().total_height() as i32 - 1 + block_position_adjust);
+    }
+
+    #[test]
+    fn test_wall_kick() {
+        let field = Field::new(FieldConfig::standard());
+        // 左の壁に付けた右向きのTブロックを左回転させると、SRSでは壁から押し出される.
+        let mut control_block = ControlBlock::new(RotationSystemType::Srs.create(), piece_set(), 1);
+        control_block.apply_block(BlockType::T, field.get_config().spawn_position());
+        assert!(control_block.rotate(&field));
+        while control_block.left(&field) {}
+        assert_eq!(control_block.orientation, Orientation::Right);
+        let left_x = control_block.position.x;
+        assert_eq!(control_block.get_last_rotation_kick(), None);
+        assert!(control_block.counter_rotate(&field));
+        assert_eq!(control_block.orientation, Orientation::Spawn);
+        assert_eq!(control_block.position.x, left_x + 1);
+        // 2番目の補正位置で回転している.下に移動すると回転の記録は消える.
+        assert_eq!(control_block.get_last_rotation_kick(), Some(1));
+        assert!(control_block.down(&field));
+        assert_eq!(control_block.get_last_rotation_kick(), None);
+
+        // 補正なしの場合は回転出来ない.
+        let mut control_block = ControlBlock::new(RotationSystemType::Classic.create(), piece_set(), 1);
+        control_block.apply_block(BlockType::T, field.get_config().spawn_position());
+        assert!(control_block.rotate(&field));
+        while control_block.left(&field) {}
+        assert!(!control_block.counter_rotate(&field));
+        assert_eq!(control_block.orientation, Orientation::Right);
+    }
+
+    #[test]
+    fn test_rotate_180() {
+        let field = Field::new(FieldConfig::standard());
+        let mut control_block = ControlBlock::new(RotationSystemType::Srs.create(), piece_set(), 1);
+        control_block.apply_block(BlockType::T, field.get_config().spawn_position());
+        assert!(control_block.rotate_180(&field));
+        assert_eq!(control_block.orientation, Orientation::Reverse);
+        assert_eq!(control_block.block, vec![
+            vec![BlockType::None, BlockType::None, BlockType::None],
+            vec![BlockType::T, BlockType::T, BlockType::T],
+            vec![BlockType::None, BlockType::T, BlockType::None],
+        ]);
+    }
+
+    #[test]
+    fn test_drop_cells() {
+        let field = Field::new(FieldConfig::standard());
+        let mut control_block = ControlBlock::new(RotationSystemType::Srs.create(), piece_set(), 1);
+        control_block.apply_block(BlockType::T, field.get_config().spawn_position());
+        let start_y = control_block.position.y;
+        assert!(control_block.soft_drop(&field));
+        assert!(control_block.down(&field));
+        assert_eq!(control_block.get_soft_drop_cells(), 1);
+        control_block.hard_drop(&field);
+        assert_eq!(control_block.get_hard_drop_cells() as i32, control_block.position.y - start_y - 2);
+        // 新しいブロックになったら数え直す.
+        control_block.apply_block(BlockType::T, field.get_config().spawn_position());
+        assert_eq!(control_block.get_soft_drop_cells(), 0);
+        assert_eq!(control_block.get_hard_drop_cells(), 0);
+    }
+
+    #[test]
+    fn test_big_block() {
+        let field = Field::new(FieldConfig::wide().big());
+        let mut control_block = ControlBlock::new(RotationSystemType::Srs.create(), piece_set(), 2);
+        control_block.apply_block(BlockType::T, field.get_config().spawn_position());
+        assert_eq!(control_block.block.len(), 6);
+        assert_eq!(control_block.unit_block(), piece_set().shape(BlockType::T));
+        // 移動は2マス単位.
+        let start = control_block.position.clone();
+        assert!(control_block.right(&field));
+        assert!(control_block.down(&field));
+        assert_eq!(control_block.position, Grid::new(start.x + 2, start.y + 2));
+        // 拡大したまま回転でき、区切りからずれない.
+        assert!(control_block.rotate(&field));
+        assert_eq!(control_block.unit_block(), vector_util::rotate_vec_90_clockwise(&piece_set().shape(BlockType::T)));
+        assert_eq!(control_block.position.x % 2, 0);
+        // 一番下まで落とすと、下端はフィールドの底に揃う.
+        control_block.hard_drop(&field);
+        let ghost = field.get_ghost_position(&control_block.block, &control_block.position);
+        assert_eq!(ghost, control_block.position);
+        let bottom = control_block.position.y - (control_block.block.len() - 1 - block_datas::calc_block_bottom(&control_block.block)) as i32;
+        assert_eq!(bottom, field.get_config().total_height() as i32 - 1);
     }
 }
@@ -53,12 +53,12 @@ mod tests {
     fn test_hold_block() {
         let mut hold_block = HoldBlock::new();
         assert_eq!(hold_block.holding_block, BlockType::None);
-        assert_eq!(hold_block.can_hold, true);
+        assert!(hold_block.can_hold);
 
         let returned_block = hold_block.hold(BlockType::T).unwrap();
         assert_eq!(returned_block, BlockType::None);
         assert_eq!(hold_block.holding_block, BlockType::T);
-        assert_eq!(hold_block.can_hold, false);
+        assert!(!hold_block.can_hold);
         
         let returned_block = hold_block.hold(BlockType::I);
         assert_eq!(returned_block, None);
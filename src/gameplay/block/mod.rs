@@ -1,4 +1,6 @@
 pub mod next_blocks;
 pub mod hold_block;
 pub mod control_block;
-pub mod block_datas;
\ No newline at end of file
+pub mod block_datas;
+pub mod rotation_system;
+pub mod piece_set;
\ No newline at end of file
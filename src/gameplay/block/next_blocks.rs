@@ -1,129 +1,213 @@
 //! 次のブロックを制御する.
-//! 全種類ランダムに1つずつ出してから、次は別の順番でまた各種1つずつ出す、という風にできる.
+//! ブロックの出し方は[Randomizer]で切り替えられる.
+//! 全種類ランダムに1つずつ出してから、次は別の順番でまた各種1つずつ出す、という風にもできる.
 use crate::gameplay::block::block_datas::BlockType;
-use rand::seq::SliceRandom;
+use rand::{Rng, rngs::StdRng, seq::SliceRandom};
+use std::cell::RefCell;
+use std::collections::VecDeque;
 
-/// 次以降に出されるブロックを管理する構造体.
-/// ブロックの種類数～種類数*2分のブロックの出す順番決められている.
-/// ブロックは自動的に補充される.
-pub struct NextBlocks {
-    bags: [Bag; 2],
-    now_bag_index: usize,
+/// 次に出すブロックを決めるトレイト.
+/// 乱数は外から渡されるので、同じシードなら同じ順番になる.
+pub trait Randomizer {
+    /// 次のブロックの種類を1つ決める.
+    fn generate(&mut self, rng: &mut StdRng) -> BlockType;
 }
 
-impl NextBlocks {
-    /// 新規インスタンスを作成する.
-    pub fn new() -> Self {
-        NextBlocks {
-            bags: [Bag::new(), Bag::new()],
-            now_bag_index: 0,
+/// バッグ方式.全種類をcopies個ずつ袋に入れて、ランダムな順番で取り出す.
+/// 袋が空になったら入れ直す.
+pub struct BagRandomizer {
+    block_types: Vec<BlockType>,
+    copies: usize,
+    bag: Vec<BlockType>,
+}
+
+impl BagRandomizer {
+    /// 新規インスタンス作成.
+    pub fn new(block_types: Vec<BlockType>, copies: usize) -> Self {
+        BagRandomizer {
+            block_types,
+            copies: copies.max(1),
+            bag: vec![],
         }
     }
-    /// 次のバッグのインデックスを返す.
-    fn next_bag_index(&self) -> usize {
-        (self.now_bag_index + 1) % 2
-    }
 
-    /// 次のブロックを出す.
-    /// ブロックは消費される.
-    pub fn next(&mut self) -> BlockType {
-        let current_bag = &mut self.bags[self.now_bag_index];
-        let next_block = current_bag.next();
-        if next_block == BlockType::None {
-            self.bags[self.now_bag_index].init();
-            self.now_bag_index = self.next_bag_index();
-            return self.bags[self.now_bag_index].next();
+    /// 中身を入れ直してランダム化する.
+    fn refill(&mut self, rng: &mut StdRng) {
+        for _ in 0..self.copies {
+            self.bag.extend(self.block_types.iter().copied());
         }
-        next_block
+        self.bag.shuffle(rng);
     }
+}
 
-    /// look_ahead個先に出される予定のブロック種類を返す.
-    /// まだ決まっていない場合はBlockType::Noneが返る.
-    /// ブロックは消費されない.
-    pub fn show_next_block(&self, look_ahead: usize) -> BlockType {
-        let current_bag = &self.bags[self.now_bag_index];
-        let current_rest = current_bag.rest();
-        if current_rest <= look_ahead {
-            let next_bag = &self.bags[self.next_bag_index()];
-            return next_bag.show_next_block(look_ahead - current_rest);
-        }
-        else{
-            return current_bag.show_next_block(look_ahead);
+impl Randomizer for BagRandomizer {
+    fn generate(&mut self, rng: &mut StdRng) -> BlockType {
+        if self.bag.is_empty() {
+            self.refill(rng);
         }
+        self.bag.pop().unwrap_or(BlockType::None)
     }
 }
 
-const BAG_SIZE: usize = 4;
-
-/// 次に出すブロックのひとかたまり.全種類1個ずつ入っている.
-struct Bag {
-    blocks: [BlockType; BAG_SIZE],
-    index: usize,
+/// 完全ランダム.毎回全種類から等確率で選ぶ.
+pub struct PureRandomizer {
+    block_types: Vec<BlockType>,
 }
 
-impl Bag {
+impl PureRandomizer {
     /// 新規インスタンス作成.
-    /// ランダム化が含まれているので、少しだけ重い？
-    pub fn new() -> Self {
-        let mut bag = Bag {
-            blocks: [
-                BlockType::I,
-                BlockType::T,
-                BlockType::J,
-                BlockType::L,
-            ],
-            index: 0,
-        };
-        bag.init();
-        bag
+    pub fn new(block_types: Vec<BlockType>) -> Self {
+        PureRandomizer { block_types }
     }
+}
 
-    /// 中身を入れ直してランダム化する.
-    pub fn init(&mut self) {
-        let mut rng = rand::rng();
-        self.blocks.shuffle(&mut rng);
-        self.index = 0;
+impl Randomizer for PureRandomizer {
+    fn generate(&mut self, rng: &mut StdRng) -> BlockType {
+        if self.block_types.is_empty() {
+            return BlockType::None;
+        }
+        self.block_types[rng.random_range(0..self.block_types.len())]
     }
+}
 
-    /// 次の中身を取り出す.
-    /// 中身は消費される.
-    pub fn next(&mut self) -> BlockType {
-        if self.index >= BAG_SIZE {
+/// 直近に出したブロックを覚えておき、被ったら決められた回数だけ選び直す.
+/// 選び直しても被った場合は、最後に選んだものを出す.
+pub struct HistoryRandomizer {
+    block_types: Vec<BlockType>,
+    history: VecDeque<BlockType>,
+    history_size: usize,
+    reroll_count: usize,
+}
+
+impl HistoryRandomizer {
+    /// 新規インスタンス作成.
+    pub fn new(block_types: Vec<BlockType>, history_size: usize, reroll_count: usize) -> Self {
+        HistoryRandomizer {
+            block_types,
+            history: VecDeque::new(),
+            history_size,
+            reroll_count,
+        }
+    }
+}
+
+impl Randomizer for HistoryRandomizer {
+    fn generate(&mut self, rng: &mut StdRng) -> BlockType {
+        if self.block_types.is_empty() {
             return BlockType::None;
         }
-        let block = self.blocks[self.index];
-        self.index += 1;
+        let mut block = self.block_types[rng.random_range(0..self.block_types.len())];
+        for _ in 0..self.reroll_count {
+            if !self.history.contains(&block) {
+                break;
+            }
+            block = self.block_types[rng.random_range(0..self.block_types.len())];
+        }
+        self.history.push_back(block);
+        while self.history.len() > self.history_size {
+            self.history.pop_front();
+        }
         block
     }
+}
 
-    /// look_ahead個先に取り出されるブロックの種類を返す.
-    /// 中身の量よりも大きい値が指定された場合はBlockType::Noneが返る.
-    /// 中身は消費されない.
-    pub fn show_next_block(&self, look_ahead: usize) -> BlockType {
-        if self.index + look_ahead >= BAG_SIZE {
-            return BlockType::None;
+/// ルールごとに選べるブロックの出し方.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RandomizerType {
+    Bag,
+    DoubleBag,
+    Random,
+    History,
+}
+
+/// 履歴方式で覚えておく数.
+const HISTORY_SIZE: usize = 4;
+/// 履歴方式で選び直す回数.
+const HISTORY_REROLL_COUNT: usize = 4;
+
+impl RandomizerType {
+    /// 対応するブロックの出し方を作る.
+    pub fn create(self, block_types: Vec<BlockType>) -> Box<dyn Randomizer + Send> {
+        match self {
+            RandomizerType::Bag => Box::new(BagRandomizer::new(block_types, 1)),
+            RandomizerType::DoubleBag => Box::new(BagRandomizer::new(block_types, 2)),
+            RandomizerType::Random => Box::new(PureRandomizer::new(block_types)),
+            RandomizerType::History => {
+                // 種類が少ないと選び直しても被りやすいので、覚えておく数は種類数未満にする.
+                let history_size = HISTORY_SIZE.min(block_types.len().saturating_sub(1));
+                Box::new(HistoryRandomizer::new(block_types, history_size, HISTORY_REROLL_COUNT))
+            },
+        }
+    }
+}
+
+/// 次以降に出されるブロックを管理する構造体.
+/// 先読みされた分だけ[Randomizer]から補充するので、いくつ先でも見ることができる.
+/// 乱数はブロック専用のものを持つので、先読みしても他の乱数の結果は変わらない.
+pub struct NextBlocks {
+    randomizer: RefCell<Box<dyn Randomizer + Send>>,
+    rng: RefCell<StdRng>,
+    queue: RefCell<VecDeque<BlockType>>,
+}
+
+impl NextBlocks {
+    /// 新規インスタンスを作成する.
+    pub fn new(randomizer: Box<dyn Randomizer + Send>, rng: StdRng) -> Self {
+        NextBlocks {
+            randomizer: RefCell::new(randomizer),
+            rng: RefCell::new(rng),
+            queue: RefCell::new(VecDeque::new()),
+        }
+    }
+
+    /// 先読み用にcount個以上溜まるまで補充する.
+    fn fill(&self, count: usize) {
+        let mut queue = self.queue.borrow_mut();
+        let mut randomizer = self.randomizer.borrow_mut();
+        let mut rng = self.rng.borrow_mut();
+        while queue.len() < count {
+            queue.push_back(randomizer.generate(&mut rng));
         }
-        self.blocks[self.index + look_ahead]
     }
-    /// 中身の残量を返す.
-    pub fn rest(&self) -> usize {
-        BAG_SIZE - self.index
+
+    /// 次のブロックを出す.
+    /// ブロックは消費される.
+    pub fn next(&mut self) -> BlockType {
+        self.fill(1);
+        self.queue.get_mut().pop_front().unwrap_or(BlockType::None)
+    }
+
+    /// look_ahead個先に出される予定のブロック種類を返す.
+    /// ブロックは消費されない.
+    pub fn show_next_block(&self, look_ahead: usize) -> BlockType {
+        self.fill(look_ahead + 1);
+        self.queue.borrow()[look_ahead]
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::SeedableRng;
+
+    const BAG_SIZE: usize = 4;
+
+    fn block_types() -> Vec<BlockType> {
+        vec![BlockType::I, BlockType::T, BlockType::J, BlockType::L]
+    }
+
+    fn count(blocks: &[BlockType], block_type: BlockType) -> usize {
+        blocks.iter().filter(|block| **block == block_type).count()
+    }
 
     #[test]
     fn test_next_blocks() {
-        let mut next_blocks = NextBlocks::new();
+        let mut next_blocks = NextBlocks::new(RandomizerType::Bag.create(block_types()), StdRng::seed_from_u64(0));
         let mut results = Vec::new();
         for _ in 0..BAG_SIZE * 2 {
             results.push(next_blocks.next());
         }
         assert_eq!(results.len(), BAG_SIZE * 2);
-        // 14個のブロックが取得できていることを確認
         for block in results {
             assert_ne!(block, BlockType::None);
         }
@@ -131,43 +215,69 @@ mod tests {
 
     #[test]
     fn test_show_next_block() {
-        let next_blocks = NextBlocks::new();
-        for i in 0..BAG_SIZE * 2 {
-            assert_ne!(next_blocks.show_next_block(i), BlockType::None);
+        let mut next_blocks = NextBlocks::new(RandomizerType::Bag.create(block_types()), StdRng::seed_from_u64(0));
+        // いくつ先でも見られて、見た通りの順番で出てくる.
+        let shown: Vec<BlockType> = (0..BAG_SIZE * 5).map(|i| next_blocks.show_next_block(i)).collect();
+        for block in shown {
+            assert_ne!(block, BlockType::None);
+            assert_eq!(next_blocks.next(), block);
         }
     }
 
     #[test]
-    fn test_bag_initialization() {
-        let bag = Bag::new();
-        assert_eq!(bag.rest(), BAG_SIZE);
+    fn test_same_seed() {
+        for randomizer_type in [RandomizerType::Bag, RandomizerType::DoubleBag, RandomizerType::Random, RandomizerType::History] {
+            let mut next_blocks1 = NextBlocks::new(randomizer_type.create(block_types()), StdRng::seed_from_u64(1234));
+            let mut next_blocks2 = NextBlocks::new(randomizer_type.create(block_types()), StdRng::seed_from_u64(1234));
+            // 先読みの仕方が違っても同じ順番になる.
+            next_blocks2.show_next_block(10);
+            for _ in 0..BAG_SIZE * 10 {
+                assert_eq!(next_blocks1.next(), next_blocks2.next());
+            }
+        }
     }
 
     #[test]
-    fn test_bag_next() {
-        let mut bag = Bag::new();
-        let first_block = bag.next();
-        assert_ne!(first_block, BlockType::None);
-        assert_eq!(bag.rest(), BAG_SIZE - 1);
+    fn test_bag_randomizer() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut randomizer = BagRandomizer::new(block_types(), 1);
+        let blocks: Vec<BlockType> = (0..BAG_SIZE).map(|_| randomizer.generate(&mut rng)).collect();
+        for block_type in block_types() {
+            assert_eq!(count(&blocks, block_type), 1);
+        }
     }
 
     #[test]
-    fn test_bag_exhaustion() {
-        let mut bag = Bag::new();
-        for _ in 0..BAG_SIZE {
-            assert_ne!(bag.next(), BlockType::None);
+    fn test_double_bag_randomizer() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut randomizer = BagRandomizer::new(block_types(), 2);
+        let blocks: Vec<BlockType> = (0..BAG_SIZE * 2).map(|_| randomizer.generate(&mut rng)).collect();
+        for block_type in block_types() {
+            assert_eq!(count(&blocks, block_type), 2);
         }
-        assert_eq!(bag.next(), BlockType::None);
-        assert_eq!(bag.rest(), 0);
     }
 
     #[test]
-    fn test_bag_show_next_block() {
-        let bag = Bag::new();
-        let first_block = bag.show_next_block(0);
-        let second_block = bag.show_next_block(1);
-        assert_ne!(first_block, BlockType::None);
-        assert_ne!(second_block, BlockType::None);
-        assert_ne!(first_block, second_block);
-    }
-}
\ No newline at end of file
+    fn test_pure_randomizer() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut randomizer = PureRandomizer::new(block_types());
+        for _ in 0..100 {
+            assert!(block_types().contains(&randomizer.generate(&mut rng)));
+        }
+        let mut randomizer = PureRandomizer::new(vec![]);
+        assert_eq!(randomizer.generate(&mut rng), BlockType::None);
+    }
+
+    #[test]
+    fn test_history_randomizer() {
+        // 選び直しが充分多ければ、覚えている分は被らない.
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut randomizer = HistoryRandomizer::new(block_types(), 3, 1000);
+        let blocks: Vec<BlockType> = (0..100).map(|_| randomizer.generate(&mut rng)).collect();
+        for window in blocks.windows(BAG_SIZE) {
+            for block_type in block_types() {
+                assert_eq!(count(window, block_type), 1);
+            }
+        }
+    }
+}
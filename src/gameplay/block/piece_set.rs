@@ -0,0 +1,349 @@
+//! ブロックセットの定義.
+//! ブロックの形、出現時の向き、出現位置のずれ、色を定義ファイルから読み込む.
+//!
+//! 定義ファイルは次のような形式.`#`で始まる行はコメント.
+//! ```text
+//! name = 3マス
+//!
+//! [piece]
+//! id = T
+//! color = 255, 0, 255
+//! spawn_orientation = spawn
+//! spawn_offset = 0, 0
+//! row = .#.
+//! row = ###
+//! row = ...
+//! ```
+
+use crate::gameplay::block::{
+    block_datas::BlockType,
+    rotation_system::Orientation,
+};
+use crate::utility::grid::Grid;
+use crate::utility::vector_util;
+use std::fs;
+use std::path::Path;
+
+/// ブロック定義ファイルを置くディレクトリ.
+pub const PIECE_SET_DIR: &str = "pieces";
+
+/// ディレクトリが無い場合に使う、埋め込みのブロックセット.
+const BUILTIN_PIECE_SETS: [&str; 3] = [
+    include_str!("../../../pieces/triomino.txt"),
+    include_str!("../../../pieces/tetromino.txt"),
+    include_str!("../../../pieces/pentomino.txt"),
+];
+
+/// ブロックの色.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PieceColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+/// 1種類のブロックの定義.
+struct PieceDefinition {
+    block_type: BlockType,
+    // 出現時の向きに回転済みの形.
+    shape: Vec<Vec<BlockType>>,
+    spawn_offset: Grid,
+    color: PieceColor,
+}
+
+/// ゲームで使うブロックの組.
+pub struct PieceSet {
+    name: String,
+    pieces: Vec<PieceDefinition>,
+}
+
+impl PieceSet {
+    /// 定義ファイルの内容を読み込んで検証する.
+    /// 不正な内容であれば、何行目がおかしいかを含めたメッセージを返す.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut name = None;
+        let mut builders: Vec<PieceBuilder> = vec![];
+        for (index, raw_line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = raw_line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line == "[piece]" {
+                builders.push(PieceBuilder::new(line_number));
+                continue;
+            }
+            let (key, value) = line.split_once('=')
+                .ok_or(format!("{}行目: 'キー = 値' の形式ではありません", line_number))?;
+            let (key, value) = (key.trim(), value.trim());
+            let Some(builder) = builders.last_mut() else {
+                if key == "name" {
+                    name = Some(value.to_string());
+                    continue;
+                }
+                return Err(format!("{}行目: [piece]の前に使えないキーです: {}", line_number, key));
+            };
+            builder.set(key, value).map_err(|message| format!("{}行目: {}", line_number, message))?;
+        }
+
+        let name = name.filter(|name| !name.is_empty()).ok_or("nameが指定されていません")?;
+        if builders.is_empty() {
+            return Err(String::from("ブロックが1つも定義されていません"));
+        }
+        let mut pieces: Vec<PieceDefinition> = vec![];
+        let mut other_count: usize = 0;
+        for builder in builders {
+            let line_number = builder.line_number;
+            let id = builder.id.clone().ok_or(format!("{}行目のブロック: idが指定されていません", line_number))?;
+            let other_index = u8::try_from(other_count).map_err(|_| String::from("ブロックの種類が多すぎます"))?;
+            let block_type = BlockType::from_id(&id, other_index);
+            if let BlockType::Other(_) = block_type {
+                other_count += 1;
+            }
+            if pieces.iter().any(|piece| piece.block_type == block_type) {
+                return Err(format!("{}行目のブロック: idが重複しています: {}", line_number, id));
+            }
+            pieces.push(builder.build(block_type).map_err(|message| format!("{}行目のブロック: {}", line_number, message))?);
+        }
+        Ok(PieceSet { name, pieces })
+    }
+
+    /// ディレクトリ内の定義ファイル(*.txt)を名前順に全て読み込む.
+    pub fn load_dir(dir: &Path) -> Result<Vec<Self>, String> {
+        let mut paths = fs::read_dir(dir)
+            .map_err(|error| format!("{}: {}", dir.display(), error))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+            .collect::<Vec<_>>();
+        paths.sort();
+        let mut piece_sets = vec![];
+        for path in paths {
+            let text = fs::read_to_string(&path).map_err(|error| format!("{}: {}", path.display(), error))?;
+            piece_sets.push(PieceSet::parse(&text).map_err(|message| format!("{}: {}", path.display(), message))?);
+        }
+        if piece_sets.is_empty() {
+            return Err(format!("{}: 定義ファイルがありません", dir.display()));
+        }
+        Ok(piece_sets)
+    }
+
+    /// 埋め込みのブロックセットを読み込む.
+    pub fn builtin() -> Vec<Self> {
+        BUILTIN_PIECE_SETS.iter()
+            .map(|text| PieceSet::parse(text).expect("埋め込みのブロック定義が不正"))
+            .collect()
+    }
+
+    /// ブロックセットの名前を返す.
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// 定義されているブロックの種類を、定義順に返す.
+    pub fn block_types(&self) -> Vec<BlockType> {
+        self.pieces.iter().map(|piece| piece.block_type).collect()
+    }
+
+    /// 種類に応じたブロックの出現時の形.
+    /// 定義されていない種類の場合は、その種類1マスだけの形を返す.
+    pub fn shape(&self, block_type: BlockType) -> Vec<Vec<BlockType>> {
+        match self.find(block_type) {
+            Some(piece) => piece.shape.clone(),
+            None => vector_util::array_to_vec_2d([[block_type]]),
+        }
+    }
+
+    /// 種類に応じた出現位置のずれ.
+    pub fn spawn_offset(&self, block_type: BlockType) -> Grid {
+        match self.find(block_type) {
+            Some(piece) => piece.spawn_offset.clone(),
+            None => Grid::new(0, 0),
+        }
+    }
+
+    /// 種類に応じたブロックの色.定義されていない種類はNone.
+    pub fn color(&self, block_type: BlockType) -> Option<PieceColor> {
+        self.find(block_type).map(|piece| piece.color)
+    }
+
+    /// 一番長いブロックの長さ.縦にすれば一度にこのライン数まで消せる.
+    /// 形は正方形で、必ず1マス以上あることを読み込み時に確認済み.
+    pub fn max_erace_lines(&self) -> u32 {
+        self.pieces.iter().map(|piece| {
+            let size = piece.shape.len();
+            let rows: Vec<usize> = (0..size).filter(|y| piece.shape[*y].iter().any(|block| *block != BlockType::None)).collect();
+            let columns: Vec<usize> = (0..size).filter(|x| piece.shape.iter().any(|line| line[*x] != BlockType::None)).collect();
+            let height = rows[rows.len() - 1] - rows[0] + 1;
+            let width = columns[columns.len() - 1] - columns[0] + 1;
+            height.max(width) as u32
+        }).max().unwrap_or(0)
+    }
+
+    fn find(&self, block_type: BlockType) -> Option<&PieceDefinition> {
+        self.pieces.iter().find(|piece| piece.block_type == block_type)
+    }
+}
+
+/// 定義ファイルの読み込み途中のブロック.
+struct PieceBuilder {
+    line_number: usize,
+    id: Option<String>,
+    color: Option<PieceColor>,
+    spawn_orientation: Orientation,
+    spawn_offset: Grid,
+    rows: Vec<String>,
+}
+
+impl PieceBuilder {
+    fn new(line_number: usize) -> Self {
+        PieceBuilder {
+            line_number,
+            id: None,
+            color: None,
+            spawn_orientation: Orientation::Spawn,
+            spawn_offset: Grid::new(0, 0),
+            rows: vec![],
+        }
+    }
+
+    /// キーに応じて値を設定する.
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "id" => {
+                if value.is_empty() {
+                    return Err(String::from("idが空です"));
+                }
+                self.id = Some(value.to_string());
+            },
+            "color" => {
+                let rgb = parse_numbers::<u8>(value, 3).ok_or(format!("色は 'r, g, b' で指定してください: {}", value))?;
+                self.color = Some(PieceColor { r: rgb[0], g: rgb[1], b: rgb[2] });
+            },
+            "spawn_orientation" => {
+                self.spawn_orientation = match value {
+                    "spawn" => Orientation::Spawn,
+                    "right" => Orientation::Right,
+                    "reverse" => Orientation::Reverse,
+                    "left" => Orientation::Left,
+                    _ => return Err(format!("向きはspawn/right/reverse/leftで指定してください: {}", value)),
+                };
+            },
+            "spawn_offset" => {
+                let offset = parse_numbers::<i32>(value, 2).ok_or(format!("出現位置のずれは 'x, y' で指定してください: {}", value))?;
+                self.spawn_offset = Grid::new(offset[0], offset[1]);
+            },
+            "row" => {
+                if value.chars().any(|c| c != '#' && c != '.') {
+                    return Err(format!("rowには#と.だけが使えます: {}", value));
+                }
+                self.rows.push(value.to_string());
+            },
+            _ => return Err(format!("不明なキーです: {}", key)),
+        }
+        Ok(())
+    }
+
+    /// 検証してブロックの定義にする.
+    fn build(self, block_type: BlockType) -> Result<PieceDefinition, String> {
+        let color = self.color.ok_or("colorが指定されていません")?;
+        let size = self.rows.len();
+        if size == 0 {
+            return Err(String::from("rowが指定されていません"));
+        }
+        if self.rows.iter().any(|row| row.chars().count() != size) {
+            return Err(String::from("形は正方形で指定してください"));
+        }
+        if !self.rows.iter().any(|row| row.contains('#')) {
+            return Err(String::from("形にブロックがありません"));
+        }
+        let mut shape: Vec<Vec<BlockType>> = self.rows.iter()
+            .map(|row| row.chars().map(|c| if c == '#' { block_type } else { BlockType::None }).collect())
+            .collect();
+        let mut orientation = Orientation::Spawn;
+        while orientation != self.spawn_orientation {
+            shape = vector_util::rotate_vec_90_clockwise(&shape);
+            orientation = orientation.clockwise();
+        }
+        Ok(PieceDefinition {
+            block_type,
+            shape,
+            spawn_offset: self.spawn_offset,
+            color,
+        })
+    }
+}
+
+/// カンマ区切りの数値をcount個読み込む.
+fn parse_numbers<T: std::str::FromStr>(value: &str, count: usize) -> Option<Vec<T>> {
+    let numbers = value.split(',')
+        .map(|number| number.trim().parse::<T>().ok())
+        .collect::<Option<Vec<T>>>()?;
+    if numbers.len() != count {
+        return None;
+    }
+    Some(numbers)
+}
+
+/// ゲーム開始時に使うブロックセットを読み込む.
+/// 定義ファイルのディレクトリがあればそこから読み込み、不正な定義があればエラーにする.
+/// ディレクトリが無ければ埋め込みのブロックセットを使う.
+pub fn load_piece_sets() -> Result<Vec<PieceSet>, String> {
+    let dir = Path::new(PIECE_SET_DIR);
+    if !dir.is_dir() {
+        return Ok(PieceSet::builtin());
+    }
+    PieceSet::load_dir(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin() {
+        let piece_sets = PieceSet::builtin();
+        assert_eq!(piece_sets.len(), 3);
+        assert_eq!(piece_sets[0].block_types(), vec![BlockType::I, BlockType::J, BlockType::L, BlockType::T]);
+        assert_eq!(piece_sets[1].block_types().len(), 7);
+        assert_eq!(piece_sets[2].block_types().len(), 12);
+
+        let t_shape = piece_sets[0].shape(BlockType::T);
+        assert_eq!(t_shape, vec![
+            vec![BlockType::None, BlockType::T, BlockType::None],
+            vec![BlockType::T, BlockType::T, BlockType::T],
+            vec![BlockType::None, BlockType::None, BlockType::None],
+        ]);
+        assert_eq!(piece_sets[0].color(BlockType::T), Some(PieceColor { r: 255, g: 0, b: 255 }));
+        assert_eq!(piece_sets[0].color(BlockType::O), None);
+        assert_eq!(piece_sets[0].shape(BlockType::None), vec![vec![BlockType::None]]);
+        assert_eq!(piece_sets[1].spawn_offset(BlockType::O), Grid::new(1, 0));
+        assert_eq!(piece_sets.iter().map(|piece_set| piece_set.max_erace_lines()).collect::<Vec<_>>(), vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn test_parse() {
+        let text = "name = test\n[piece]\nid = X\ncolor = 1, 2, 3\nspawn_orientation = right\nrow = #.\nrow = ..\n";
+        let piece_set = PieceSet::parse(text).unwrap();
+        assert_eq!(piece_set.get_name(), "test");
+        assert_eq!(piece_set.block_types(), vec![BlockType::Other(0)]);
+        // 出現時の向きに回転している.
+        assert_eq!(piece_set.shape(BlockType::Other(0)), vec![
+            vec![BlockType::None, BlockType::Other(0)],
+            vec![BlockType::None, BlockType::None],
+        ]);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert!(PieceSet::parse("[piece]\nid = T\ncolor = 1, 2, 3\nrow = #\n").is_err());
+        assert!(PieceSet::parse("name = test\n").is_err());
+        assert!(PieceSet::parse("name = test\n[piece]\nid = T\nrow = #\n").is_err());
+        assert!(PieceSet::parse("name = test\n[piece]\nid = T\ncolor = 1, 2\nrow = #\n").is_err());
+        assert!(PieceSet::parse("name = test\n[piece]\nid = T\ncolor = 1, 2, 3\nrow = ##\nrow = #\n").is_err());
+        assert!(PieceSet::parse("name = test\n[piece]\nid = T\ncolor = 1, 2, 3\nrow = .\n").is_err());
+        assert!(PieceSet::parse("name = test\n[piece]\nid = T\ncolor = 1, 2, 3\nsize = 1\nrow = #\n").is_err());
+        let duplicated = "name = test\n[piece]\nid = T\ncolor = 1, 2, 3\nrow = #\n[piece]\nid = T\ncolor = 1, 2, 3\nrow = #\n";
+        let message = PieceSet::parse(duplicated).err().unwrap();
+        assert!(message.starts_with("6行目"));
+    }
+}
@@ -0,0 +1,241 @@
+//! ブロックの回転法則.
+//! 回転出来ない場合に、どの位置へずらして回転を試すか(壁蹴り)を表で定義する.
+//! 補正位置はフィールドと同じく、下向きを正とする.
+
+use crate::gameplay::block::block_datas::BlockType;
+use crate::utility::grid::Grid;
+use std::sync::Arc;
+
+/// ブロックの向き.出現時の向きを基準とする.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Orientation {
+    Spawn,
+    Right,
+    Reverse,
+    Left,
+}
+
+impl Orientation {
+    /// 右回転した後の向きを返す.
+    pub fn clockwise(self) -> Self {
+        match self {
+            Orientation::Spawn => Orientation::Right,
+            Orientation::Right => Orientation::Reverse,
+            Orientation::Reverse => Orientation::Left,
+            Orientation::Left => Orientation::Spawn,
+        }
+    }
+
+    /// 左回転した後の向きを返す.
+    pub fn counter_clockwise(self) -> Self {
+        match self {
+            Orientation::Spawn => Orientation::Left,
+            Orientation::Right => Orientation::Spawn,
+            Orientation::Reverse => Orientation::Right,
+            Orientation::Left => Orientation::Reverse,
+        }
+    }
+
+    /// 180度回転した後の向きを返す.
+    pub fn opposite(self) -> Self {
+        self.clockwise().clockwise()
+    }
+
+    /// 表を引くためのインデックス.
+    fn index(self) -> usize {
+        match self {
+            Orientation::Spawn => 0,
+            Orientation::Right => 1,
+            Orientation::Reverse => 2,
+            Orientation::Left => 3,
+        }
+    }
+}
+
+/// 回転の補正表.回転前の向きごとに、試す補正位置を先頭から並べる.
+pub struct KickTable {
+    clockwise: [&'static [Grid]; 4],
+    counter_clockwise: [&'static [Grid]; 4],
+    half_turn: [&'static [Grid]; 4],
+}
+
+impl KickTable {
+    /// fromからtoへ回転する場合の補正位置の一覧を返す.
+    /// 表に無い回転の場合は空になる.
+    pub fn get(&self, from: Orientation, to: Orientation) -> &'static [Grid] {
+        if to == from.clockwise() {
+            self.clockwise[from.index()]
+        }
+        else if to == from.counter_clockwise() {
+            self.counter_clockwise[from.index()]
+        }
+        else if to == from.opposite() {
+            self.half_turn[from.index()]
+        }
+        else {
+            &[]
+        }
+    }
+}
+
+/// 回転法則のトレイト.
+pub trait RotationSystem {
+    /// ブロックの種類に応じた補正表を返す.
+    fn kick_table(&self, block_type: BlockType) -> &KickTable;
+
+    /// fromからtoへ回転する場合に試す補正位置の一覧を返す.
+    fn kicks(&self, block_type: BlockType, from: Orientation, to: Orientation) -> &'static [Grid] {
+        self.kick_table(block_type).get(from, to)
+    }
+}
+
+/// SRS風の補正表.Iブロック以外.
+static SRS_KICK_TABLE: KickTable = KickTable {
+    clockwise: [
+        &[Grid::new(0, 0), Grid::new(-1, 0), Grid::new(-1, -1), Grid::new(0, 2), Grid::new(-1, 2)],
+        &[Grid::new(0, 0), Grid::new(1, 0), Grid::new(1, 1), Grid::new(0, -2), Grid::new(1, -2)],
+        &[Grid::new(0, 0), Grid::new(1, 0), Grid::new(1, -1), Grid::new(0, 2), Grid::new(1, 2)],
+        &[Grid::new(0, 0), Grid::new(-1, 0), Grid::new(-1, 1), Grid::new(0, -2), Grid::new(-1, -2)],
+    ],
+    counter_clockwise: [
+        &[Grid::new(0, 0), Grid::new(1, 0), Grid::new(1, -1), Grid::new(0, 2), Grid::new(1, 2)],
+        &[Grid::new(0, 0), Grid::new(1, 0), Grid::new(1, 1), Grid::new(0, -2), Grid::new(1, -2)],
+        &[Grid::new(0, 0), Grid::new(-1, 0), Grid::new(-1, -1), Grid::new(0, 2), Grid::new(-1, 2)],
+        &[Grid::new(0, 0), Grid::new(-1, 0), Grid::new(-1, 1), Grid::new(0, -2), Grid::new(-1, -2)],
+    ],
+    half_turn: SRS_HALF_TURN_KICKS,
+};
+
+/// SRS風の180度回転の補正.全てのブロックで共通.
+const SRS_HALF_TURN_KICKS: [&[Grid]; 4] = [
+    &[Grid::new(0, 0), Grid::new(0, -1), Grid::new(1, -1), Grid::new(-1, -1), Grid::new(1, 0), Grid::new(-1, 0)],
+    &[Grid::new(0, 0), Grid::new(1, 0), Grid::new(1, -2), Grid::new(1, -1), Grid::new(0, -2), Grid::new(0, -1)],
+    &[Grid::new(0, 0), Grid::new(0, 1), Grid::new(-1, 1), Grid::new(1, 1), Grid::new(-1, 0), Grid::new(1, 0)],
+    &[Grid::new(0, 0), Grid::new(-1, 0), Grid::new(-1, -2), Grid::new(-1, -1), Grid::new(0, -2), Grid::new(0, -1)],
+];
+
+/// SRS風の補正表.Iブロック用.
+static SRS_I_KICK_TABLE: KickTable = KickTable {
+    clockwise: [
+        &[Grid::new(0, 0), Grid::new(-2, 0), Grid::new(1, 0), Grid::new(-2, 1), Grid::new(1, -2)],
+        &[Grid::new(0, 0), Grid::new(-1, 0), Grid::new(2, 0), Grid::new(-1, -2), Grid::new(2, 1)],
+        &[Grid::new(0, 0), Grid::new(2, 0), Grid::new(-1, 0), Grid::new(2, -1), Grid::new(-1, 2)],
+        &[Grid::new(0, 0), Grid::new(1, 0), Grid::new(-2, 0), Grid::new(1, 2), Grid::new(-2, -1)],
+    ],
+    counter_clockwise: [
+        &[Grid::new(0, 0), Grid::new(-1, 0), Grid::new(2, 0), Grid::new(-1, -2), Grid::new(2, 1)],
+        &[Grid::new(0, 0), Grid::new(2, 0), Grid::new(-1, 0), Grid::new(2, -1), Grid::new(-1, 2)],
+        &[Grid::new(0, 0), Grid::new(1, 0), Grid::new(-2, 0), Grid::new(1, 2), Grid::new(-2, -1)],
+        &[Grid::new(0, 0), Grid::new(-2, 0), Grid::new(1, 0), Grid::new(-2, 1), Grid::new(1, -2)],
+    ],
+    half_turn: SRS_HALF_TURN_KICKS,
+};
+
+/// 補正なしの表.その場で回転出来なければ回転しない.
+static CLASSIC_KICK_TABLE: KickTable = KickTable {
+    clockwise: [&[Grid::new(0, 0)]; 4],
+    counter_clockwise: [&[Grid::new(0, 0)]; 4],
+    half_turn: [&[Grid::new(0, 0)]; 4],
+};
+
+/// ARS風の補正表.その場で回転出来なければ右、左の順に1マスずらして試す.
+static ARS_KICK_TABLE: KickTable = KickTable {
+    clockwise: [&[Grid::new(0, 0), Grid::new(1, 0), Grid::new(-1, 0)]; 4],
+    counter_clockwise: [&[Grid::new(0, 0), Grid::new(1, 0), Grid::new(-1, 0)]; 4],
+    half_turn: [&[Grid::new(0, 0), Grid::new(1, 0), Grid::new(-1, 0)]; 4],
+};
+
+/// ARS風の補正表.Tブロック用.左右に加えて1マス上に持ち上げる床蹴りがある.
+static ARS_T_KICK_TABLE: KickTable = KickTable {
+    clockwise: [&[Grid::new(0, 0), Grid::new(1, 0), Grid::new(-1, 0), Grid::new(0, -1)]; 4],
+    counter_clockwise: [&[Grid::new(0, 0), Grid::new(1, 0), Grid::new(-1, 0), Grid::new(0, -1)]; 4],
+    half_turn: [&[Grid::new(0, 0), Grid::new(1, 0), Grid::new(-1, 0), Grid::new(0, -1)]; 4],
+};
+
+/// SRS風の回転法則.向きごとに5通りの補正を試す.
+pub struct SrsRotationSystem {}
+impl RotationSystem for SrsRotationSystem {
+    fn kick_table(&self, block_type: BlockType) -> &KickTable {
+        match block_type {
+            BlockType::I => &SRS_I_KICK_TABLE,
+            _ => &SRS_KICK_TABLE,
+        }
+    }
+}
+
+/// 補正なしのクラシックな回転法則.
+pub struct ClassicRotationSystem {}
+impl RotationSystem for ClassicRotationSystem {
+    fn kick_table(&self, _: BlockType) -> &KickTable {
+        &CLASSIC_KICK_TABLE
+    }
+}
+
+/// ARS風の回転法則.Iブロックは補正しない.
+pub struct ArsRotationSystem {}
+impl RotationSystem for ArsRotationSystem {
+    fn kick_table(&self, block_type: BlockType) -> &KickTable {
+        match block_type {
+            BlockType::I => &CLASSIC_KICK_TABLE,
+            BlockType::T => &ARS_T_KICK_TABLE,
+            _ => &ARS_KICK_TABLE,
+        }
+    }
+}
+
+/// ゲームごとに選べる回転法則の種類.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RotationSystemType {
+    Srs,
+    Classic,
+    Ars,
+}
+
+impl RotationSystemType {
+    /// 対応する回転法則を作る.
+    pub fn create(self) -> Arc<dyn RotationSystem + Send + Sync> {
+        match self {
+            RotationSystemType::Srs => Arc::new(SrsRotationSystem {}),
+            RotationSystemType::Classic => Arc::new(ClassicRotationSystem {}),
+            RotationSystemType::Ars => Arc::new(ArsRotationSystem {}),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_orientation() {
+        let mut orientation = Orientation::Spawn;
+        for _ in 0..4 {
+            orientation = orientation.clockwise();
+        }
+        assert_eq!(orientation, Orientation::Spawn);
+        assert_eq!(Orientation::Spawn.counter_clockwise(), Orientation::Left);
+        assert_eq!(Orientation::Right.clockwise().counter_clockwise(), Orientation::Right);
+        assert_eq!(Orientation::Left.opposite(), Orientation::Right);
+    }
+
+    #[test]
+    fn test_kicks() {
+        let srs = SrsRotationSystem {};
+        // 向きによって補正が変わる.
+        assert_ne!(srs.kicks(BlockType::T, Orientation::Spawn, Orientation::Right),
+                   srs.kicks(BlockType::T, Orientation::Right, Orientation::Reverse));
+        assert_eq!(srs.kicks(BlockType::J, Orientation::Spawn, Orientation::Right)[1], Grid::new(-1, 0));
+        assert_eq!(srs.kicks(BlockType::I, Orientation::Spawn, Orientation::Right)[1], Grid::new(-2, 0));
+        // 180度回転は専用の補正を使う.
+        assert_eq!(srs.kicks(BlockType::T, Orientation::Spawn, Orientation::Reverse)[1], Grid::new(0, -1));
+        // 表に無い回転は補正も無い.
+        assert!(srs.kicks(BlockType::T, Orientation::Spawn, Orientation::Spawn).is_empty());
+
+        let classic = ClassicRotationSystem {};
+        assert_eq!(classic.kicks(BlockType::T, Orientation::Left, Orientation::Spawn), &[Grid::new(0, 0)]);
+
+        let ars = ArsRotationSystem {};
+        assert_eq!(ars.kicks(BlockType::I, Orientation::Spawn, Orientation::Right).len(), 1);
+        assert_eq!(ars.kicks(BlockType::L, Orientation::Spawn, Orientation::Left).len(), 3);
+    }
+}
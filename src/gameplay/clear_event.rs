@@ -0,0 +1,122 @@
+//! ブロックを固定したときの消し方.
+//! スコアや攻撃力の計算、ステータスの集計はこれを見て行う.
+
+use crate::gameplay::{block::block_datas::BlockType, t_spin_checker::TSpinType};
+
+/// 1回の固定で起きた消去の情報.ラインを消さなかったTスピンも含む.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ClearEvent {
+    /// 消したライン数.
+    pub lines: u32,
+    /// スピンの種類.Tブロック以外のスピンも含む.
+    pub t_spin: TSpinType,
+    /// スピンしたブロックの種類.スピンでなければNone.
+    pub spin_block: BlockType,
+    /// 連続で消した回数.最初の消去で1、消さなかった場合は0.
+    pub combo: u32,
+    /// 難しい消し方が続いたかどうか.
+    pub back_to_back: bool,
+    /// 消した結果フィールドが空になったかどうか.
+    pub perfect_clear: bool,
+    /// 連鎖の段数.ブロックの固定で消した場合は0、落下で続けて消すたびに1ずつ増える.
+    pub chain: u32,
+}
+
+/// 固定のたびに消し方を分類する.コンボとバックトゥバックの続き具合を覚えておく.
+pub struct ClearClassifier {
+    max_lines: u32,
+    combo: u32,
+    back_to_back_ready: bool,
+}
+
+impl ClearClassifier {
+    /// 新規インスタンス作成.
+    /// max_lines以上を一度に消すか、スピンで消すと難しい消し方とする.
+    pub fn new(max_lines: u32) -> Self {
+        ClearClassifier {
+            max_lines,
+            combo: 0,
+            back_to_back_ready: false,
+        }
+    }
+
+    /// 消したライン数、スピンの種類とスピンしたブロック、消した後にフィールドが空かどうかから分類する.
+    /// ラインを消さなかった場合はコンボが途切れるが、バックトゥバックは途切れない.
+    pub fn classify(&mut self, lines: u32, t_spin: TSpinType, spin_block: BlockType, field_empty: bool) -> ClearEvent {
+        let mut back_to_back = false;
+        if lines > 0 {
+            self.combo += 1;
+            let is_difficult = t_spin != TSpinType::None || lines >= self.max_lines;
+            back_to_back = is_difficult && self.back_to_back_ready;
+            self.back_to_back_ready = is_difficult;
+        }
+        else {
+            self.combo = 0;
+        }
+        ClearEvent {
+            lines,
+            t_spin,
+            spin_block,
+            combo: self.combo,
+            back_to_back,
+            perfect_clear: lines > 0 && field_empty,
+            chain: 0,
+        }
+    }
+
+    /// ライン消去後の落下で揃ったラインを連鎖として分類する.chainは1から数えた連鎖の段数.
+    /// 固定による消去ではないので、コンボとバックトゥバックの続き具合は変えない.
+    /// コンボの分は固定した時の消去で数えているので、連鎖ではコンボを0にして二重に数えないようにする.
+    pub fn classify_chain(&self, lines: u32, chain: u32, field_empty: bool) -> ClearEvent {
+        ClearEvent {
+            lines,
+            t_spin: TSpinType::None,
+            spin_block: BlockType::None,
+            combo: 0,
+            back_to_back: false,
+            perfect_clear: lines > 0 && field_empty,
+            chain,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let mut classifier = ClearClassifier::new(4);
+        let event = classifier.classify(4, TSpinType::None, BlockType::None, false);
+        assert_eq!(event.combo, 1);
+        assert!(!event.back_to_back);
+        // ラインを消さない固定ではバックトゥバックは途切れない.
+        let event = classifier.classify(0, TSpinType::None, BlockType::None, true);
+        assert_eq!(event.combo, 0);
+        assert!(!event.perfect_clear);
+        let event = classifier.classify(2, TSpinType::Full, BlockType::T, true);
+        assert!(event.back_to_back);
+        assert!(event.perfect_clear);
+        let event = classifier.classify(1, TSpinType::None, BlockType::None, false);
+        assert_eq!(event.combo, 2);
+        assert!(!event.back_to_back);
+        // 簡単な消し方を挟むと途切れる.
+        let event = classifier.classify(4, TSpinType::None, BlockType::None, false);
+        assert!(!event.back_to_back);
+        assert_eq!(event.chain, 0);
+    }
+
+    #[test]
+    fn test_classify_chain() {
+        let mut classifier = ClearClassifier::new(4);
+        classifier.classify(4, TSpinType::None, BlockType::None, false);
+        let event = classifier.classify_chain(2, 1, true);
+        assert_eq!(event.chain, 1);
+        assert_eq!(event.combo, 0);
+        assert!(event.perfect_clear);
+        // 連鎖を挟んでもバックトゥバックは続く.
+        let event = classifier.classify(4, TSpinType::None, BlockType::None, false);
+        assert!(event.back_to_back);
+        assert_eq!(event.combo, 2);
+    }
+}
@@ -3,33 +3,49 @@
 use crate::{
     gameplay::{
         block::{
-            block_datas::{self, BlockType}, control_block::ControlBlock, hold_block::HoldBlock, next_blocks::NextBlocks
+            block_datas::{self, BlockType}, control_block::ControlBlock, hold_block::HoldBlock, next_blocks::NextBlocks,
+            piece_set::PieceSet,
         },
-        field::{self, Field},
+        field::Field,
+        game_clock::GameClock,
+        handling_settings::{HandlingSettings, ARR_INSTANT, SOFT_DROP_INFINITE},
         key_input::{KeyType, KeyInput},    
+        replay::ReplayAction,
     }, 
     utility::{
         vector_util,
         grid::Grid,
     },
 };
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::sync::{Arc, Mutex};
 
-const AUTO_REPEAT_INITIAL_DELAY_MS: u64 = 300;
-const AUTO_HORIZONTAL_REPEAT_INTERVAL_MS: u64 = 500 / field::FIELD_WIDTH as u64;
-const DROP_VELOCITY_MULTIPLIER: u128 = 20;
 pub const HARD_DROP_MOVE_COUNT: i32 = 999;
 pub const HOLD_USING: i32 = -1;
 
 /// ゲームをコントロールするトレイト
 pub trait PlayController {
-    fn plan(&mut self, _: &BlockType, _: &BlockType, _: &Field) { }
+    /// 更新のはじめに、何フレーム目かを伝える.
+    fn begin_frame(&mut self, _: u64) { }
+    fn plan(&mut self, _: &BlockType, _: &BlockType, _: &Field, _: &PieceSet) { }
     fn control(&mut self, target: &mut ControlBlock, hold_block: &mut HoldBlock, field: &Field, next_blocks: &mut NextBlocks, drop_time_ms: u128, auto_drop_count: u32) -> i32;
+    /// 操作できない間(ブロックが出るまでやライン消去中)に毎フレーム呼ばれる.
+    /// 押されたキーを覚えておき、操作できるようになったら使う.
+    fn buffer_input(&mut self) { }
+    /// ブロックが出た瞬間に呼ばれる.出現時の回転とホールド(IRS/IHS)を行う.
+    fn spawn_control(&mut self, _: &mut ControlBlock, _: &mut HoldBlock, _: &Field, _: &mut NextBlocks) { }
     fn is_pause_requested(&self) -> bool;
+    /// 攻撃先の選び方の切り替えが要求されたかどうか.
+    fn is_target_change_requested(&self) -> bool {
+        false
+    }
     fn is_player_exists(&self) -> bool {
         true
     }
+    /// このフレームでブロックに加えた操作を、行った順に取り出す.リプレイの記録に使う.
+    fn take_applied_actions(&mut self) -> Vec<ReplayAction> {
+        vec![]
+    }
 }
 
 /// プレイヤーが操作に使うキー設定.
@@ -39,9 +55,11 @@ pub struct PlayerKeyAssigns {
     down: KeyType,
     rotate: KeyType,
     counter_rotate: KeyType,
+    rotate_180: KeyType,
     hard_drop: KeyType,
     hold: KeyType,
     pause: KeyType,
+    change_target: KeyType,
 }
 
 impl PlayerKeyAssigns {
@@ -52,9 +70,11 @@ impl PlayerKeyAssigns {
             down: KeyType::P1Down,
             rotate: KeyType::P1Rotate,
             counter_rotate: KeyType::P1CounterRotate,
+            rotate_180: KeyType::P1Rotate180,
             hard_drop: KeyType::P1HardDrop,
             hold: KeyType::P1Hold,
             pause: KeyType::P1Pause,
+            change_target: KeyType::P1ChangeTarget,
         }
     }
     pub fn player2_keys() -> Self {
@@ -64,68 +84,203 @@ impl PlayerKeyAssigns {
             down: KeyType::P2Down,
             rotate: KeyType::P2Rotate,
             counter_rotate: KeyType::P2CounterRotate,
+            rotate_180: KeyType::P2Rotate180,
             hard_drop: KeyType::P2HardDrop,
             hold: KeyType::P2Hold,
             pause: KeyType::P2Pause,
+            change_target: KeyType::P2ChangeTarget,
         }
     }
 }
 
+/// 操作できない間に押され、後で使う入力.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum BufferedInput {
+    Left,
+    Right,
+    Rotate,
+    CounterRotate,
+    Rotate180,
+    HardDrop,
+    Hold,
+}
+
 /// プレイヤーが操作する場合に使用する構造体.
 pub struct PlayerController {
     keys: PlayerKeyAssigns,
+    handling: HandlingSettings,
     repeat_counter_left: u32,
     repeat_counter_right: u32,
     repeat_counter_down: u32,
+    spawn_time: Instant,
+    // 横移動とソフトドロップのキーを押し始めた時刻.インゲームの時計で測り、離すとNoneに戻る.
+    left_pressed_at: Option<Instant>,
+    right_pressed_at: Option<Instant>,
+    down_pressed_at: Option<Instant>,
+    clock: Arc<dyn GameClock + Send + Sync>,
+    buffered_inputs: Vec<BufferedInput>,
+    applied_actions: Vec<ReplayAction>,
     key_input:  Arc<Mutex<dyn KeyInput + Send>>,
 }
 
 impl PlayerController {
     /// 新規インスタンス作成.
-    pub fn new(key_assigns: PlayerKeyAssigns, key_input: Arc<Mutex<dyn KeyInput + Send>>) -> Self {
+    /// clockはブロックが出てからの時間と、キーを押している時間を測るのに使う.インゲームと同じ時計を渡す.
+    pub fn new(key_assigns: PlayerKeyAssigns, handling: HandlingSettings, key_input: Arc<Mutex<dyn KeyInput + Send>>, clock: Arc<dyn GameClock + Send + Sync>) -> Self {
         PlayerController {
             keys: key_assigns,
+            handling,
             repeat_counter_left: 0,
             repeat_counter_right: 0,
             repeat_counter_down: 0,
+            spawn_time: clock.now(),
+            left_pressed_at: None,
+            right_pressed_at: None,
+            down_pressed_at: None,
+            clock,
+            buffered_inputs: vec![],
+            applied_actions: vec![],
             key_input: key_input,
         }
     }
+
+    /// キーを押し始めた時刻からの経過時間.押していなければ0.
+    fn calc_press_time(&self, pressed_at: Option<Instant>) -> Duration {
+        pressed_at.map_or(Duration::ZERO, |pressed_at| self.clock.now().duration_since(pressed_at))
+    }
 }
 
 impl PlayController for PlayerController {
+    /// 横移動とソフトドロップのキーを押し始めた時刻を、インゲームの時計で覚えておく.
+    /// ポーズ中やゲームの外ではインゲームの時計が止まっているので、その間押していた時間は数えない.
+    fn begin_frame(&mut self, _: u64) {
+        let now = self.clock.now();
+        let key_input = self.key_input.lock().unwrap();
+        let keys = [
+            (&self.keys.left, &mut self.left_pressed_at),
+            (&self.keys.right, &mut self.right_pressed_at),
+            (&self.keys.down, &mut self.down_pressed_at),
+        ];
+        for (key, pressed_at) in keys {
+            if key_input.is_press(key) {
+                pressed_at.get_or_insert(now);
+            }
+            else {
+                *pressed_at = None;
+            }
+        }
+    }
+
+    /// ブロックが出た時刻を覚えておく.DASの溜めを持ち越さない場合はリピートもやり直す.
+    fn plan(&mut self, _: &BlockType, _: &BlockType, _: &Field, _: &PieceSet) {
+        self.spawn_time = self.clock.now();
+        if !self.handling.keep_das_charge {
+            self.repeat_counter_left = 0;
+            self.repeat_counter_right = 0;
+        }
+    }
+
+    /// 操作できない間に押されたキーを、押された順に覚えておく.
+    fn buffer_input(&mut self) {
+        let key_input = self.key_input.lock().unwrap();
+        let keys = [
+            (&self.keys.left, BufferedInput::Left),
+            (&self.keys.right, BufferedInput::Right),
+            (&self.keys.rotate, BufferedInput::Rotate),
+            (&self.keys.counter_rotate, BufferedInput::CounterRotate),
+            (&self.keys.rotate_180, BufferedInput::Rotate180),
+            (&self.keys.hard_drop, BufferedInput::HardDrop),
+            (&self.keys.hold, BufferedInput::Hold),
+        ];
+        for (key, input) in keys {
+            if key_input.is_down(key) {
+                self.buffered_inputs.push(input);
+            }
+        }
+    }
+
+    /// 出現時のホールドと回転.
+    /// 押し続けているキーを優先し、無ければ覚えておいた入力を使う.
+    /// 横移動とハードドロップは、最初の操作フレームに回す.
+    fn spawn_control(&mut self, target: &mut ControlBlock, hold_block: &mut HoldBlock, field: &Field, next_blocks: &mut NextBlocks) {
+        let (hold_press, rotate_press, counter_rotate_press, rotate_180_press) = {
+            let key_input = self.key_input.lock().unwrap();
+            (key_input.is_press(&self.keys.hold), key_input.is_press(&self.keys.rotate), key_input.is_press(&self.keys.counter_rotate), key_input.is_press(&self.keys.rotate_180))
+        };
+        let buffered_inputs = std::mem::take(&mut self.buffered_inputs);
+        if (hold_press || buffered_inputs.contains(&BufferedInput::Hold)) && apply_hold(target, hold_block, field, next_blocks) == HOLD_USING {
+            self.applied_actions.push(ReplayAction::Hold);
+        }
+        let rotation = if rotate_press {
+                Some(BufferedInput::Rotate)
+            }
+            else if counter_rotate_press {
+                Some(BufferedInput::CounterRotate)
+            }
+            else if rotate_180_press {
+                Some(BufferedInput::Rotate180)
+            }
+            else {
+                buffered_inputs.iter().rev().copied()
+                    .find(|input| matches!(input, BufferedInput::Rotate | BufferedInput::CounterRotate | BufferedInput::Rotate180))
+            };
+        let rotated = match rotation {
+            Some(BufferedInput::Rotate) if target.rotate(field) => Some(ReplayAction::Rotate),
+            Some(BufferedInput::CounterRotate) if target.counter_rotate(field) => Some(ReplayAction::CounterRotate),
+            Some(BufferedInput::Rotate180) if target.rotate_180(field) => Some(ReplayAction::Rotate180),
+            _ => None,
+        };
+        self.applied_actions.extend(rotated);
+        self.buffered_inputs = buffered_inputs.into_iter()
+            .filter(|input| matches!(input, BufferedInput::Left | BufferedInput::Right | BufferedInput::HardDrop))
+            .collect();
+    }
+
     /// [ControlBlock]を操作する.
     /// 操作を加えたことで変更された場合に、変更回数を返す.
     /// ただし、ハードドロップが行われた場合には充分大きい値が返る.
     fn control(&mut self, target: &mut ControlBlock, hold_block: &mut HoldBlock, field: &Field, next_blocks: &mut NextBlocks, drop_time_ms: u128, auto_drop_count: u32) -> i32 {
         let mut move_count = 0;
+        let since_spawn = self.clock.now().duration_since(self.spawn_time);
+        // 操作できない間に押された横移動とハードドロップを先に使う.
+        let mut buffered_hard_drop = false;
+        for input in std::mem::take(&mut self.buffered_inputs) {
+            match input {
+                BufferedInput::Left if target.left(field) => {
+                    move_count += 1;
+                    self.applied_actions.push(ReplayAction::Left);
+                },
+                BufferedInput::Right if target.right(field) => {
+                    move_count += 1;
+                    self.applied_actions.push(ReplayAction::Right);
+                },
+                BufferedInput::HardDrop => buffered_hard_drop = true,
+                _ => {},
+            }
+        }
         let (left_down, left_press, right_down, right_press, 
-            down_press, rotate_down, counter_rotate_down, 
+            down_press, rotate_down, counter_rotate_down, rotate_180_down,
             hard_drop_down, hold_down) = {
             let key_input = self.key_input.lock().unwrap();
             (key_input.is_down(&self.keys.left), key_input.is_press(&self.keys.left), key_input.is_down(&self.keys.right), key_input.is_press(&self.keys.right), 
-                key_input.is_press(&self.keys.down), key_input.is_down(&self.keys.rotate), key_input.is_down(&self.keys.counter_rotate), 
+                key_input.is_press(&self.keys.down), key_input.is_down(&self.keys.rotate), key_input.is_down(&self.keys.counter_rotate), key_input.is_down(&self.keys.rotate_180),
                 key_input.is_down(&self.keys.hard_drop), key_input.is_down(&self.keys.hold))
         };
         if left_down {
             if target.left(field) {
                 move_count += 1;
+                self.applied_actions.push(ReplayAction::Left);
             };
         }
         if left_press {
-            let press_time = {
-                let key_input = self.key_input.lock().unwrap();
-                key_input.calc_elapsed(&self.keys.left)
-            };
-            if press_time > Duration::from_millis(AUTO_REPEAT_INITIAL_DELAY_MS) {
-                let repeat_count = (press_time.as_millis() - AUTO_REPEAT_INITIAL_DELAY_MS as u128)
-                    / AUTO_HORIZONTAL_REPEAT_INTERVAL_MS as u128 - self.repeat_counter_left as u128;
-                for _ in 0..repeat_count {
-                    self.repeat_counter_left += 1;
-                    if target.left(field) {
-                        move_count += 1;
-                    };
+            let press_time = self.calc_press_time(self.left_pressed_at);
+            let repeat_count = calc_horizontal_repeat(&self.handling, press_time, since_spawn, &mut self.repeat_counter_left);
+            for _ in 0..repeat_count {
+                if !target.left(field) {
+                    break;
                 }
+                move_count += 1;
+                self.applied_actions.push(ReplayAction::Left);
             }
         }
         else{
@@ -134,39 +289,43 @@ impl PlayController for PlayerController {
         if right_down {
             if target.right(field) {
                 move_count += 1;
+                self.applied_actions.push(ReplayAction::Right);
             };
         }
         if right_press {
-            let press_time = {
-                let key_input = self.key_input.lock().unwrap();
-                key_input.calc_elapsed(&self.keys.right)
-            };
-            if press_time > Duration::from_millis(AUTO_REPEAT_INITIAL_DELAY_MS) {
-                let repeat_count = (press_time.as_millis() - AUTO_REPEAT_INITIAL_DELAY_MS as u128)
-                    / AUTO_HORIZONTAL_REPEAT_INTERVAL_MS as u128 - self.repeat_counter_right as u128;
-                for _ in 0..repeat_count {
-                    self.repeat_counter_right += 1;
-                    if target.right(field) {
-                        move_count += 1;
-                    };
+            let press_time = self.calc_press_time(self.right_pressed_at);
+            let repeat_count = calc_horizontal_repeat(&self.handling, press_time, since_spawn, &mut self.repeat_counter_right);
+            for _ in 0..repeat_count {
+                if !target.right(field) {
+                    break;
                 }
+                move_count += 1;
+                self.applied_actions.push(ReplayAction::Right);
             }
         }
         else{
             self.repeat_counter_right = 0;
         }
         if down_press {
-            let press_time = {
-                let key_input = self.key_input.lock().unwrap();
-                key_input.calc_elapsed(&self.keys.down)
-            };
-            let mut repeat_count = (press_time.as_millis() * DROP_VELOCITY_MULTIPLIER / drop_time_ms) as u32 - self.repeat_counter_down;
-            if repeat_count > 0 {
-                repeat_count -=  auto_drop_count;
+            if self.handling.soft_drop_factor == SOFT_DROP_INFINITE {
+                while target.soft_drop(field) {
+                    self.applied_actions.push(ReplayAction::SoftDrop);
+                }
             }
-            for _ in 0..repeat_count {
-                self.repeat_counter_down += 1;
-                target.down(field);
+            else {
+                let press_time = self.calc_press_time(self.down_pressed_at);
+                let total_count = (press_time.as_millis() * self.handling.soft_drop_factor as u128 / drop_time_ms) as u32;
+                let mut repeat_count = total_count.saturating_sub(self.repeat_counter_down);
+                if repeat_count > 0 {
+                    // 落下速度が速いと自動落下の方が多くなることがある.
+                    repeat_count = repeat_count.saturating_sub(auto_drop_count);
+                }
+                for _ in 0..repeat_count {
+                    self.repeat_counter_down += 1;
+                    if target.soft_drop(field) {
+                        self.applied_actions.push(ReplayAction::SoftDrop);
+                    }
+                }
             }
         }
         else{
@@ -175,27 +334,50 @@ impl PlayController for PlayerController {
         if rotate_down {
             if target.rotate(field) {
                 move_count += 1;
+                self.applied_actions.push(ReplayAction::Rotate);
             }
         }
         if counter_rotate_down {
             if target.counter_rotate(field) {
                 move_count += 1;
+                self.applied_actions.push(ReplayAction::CounterRotate);
+            }
+        }
+        if rotate_180_down {
+            if target.rotate_180(field) {
+                move_count += 1;
+                self.applied_actions.push(ReplayAction::Rotate180);
             }
         }
-        if hard_drop_down {
+        if hard_drop_down || buffered_hard_drop {
             target.hard_drop(field);
             move_count += HARD_DROP_MOVE_COUNT;
+            self.applied_actions.push(ReplayAction::HardDrop);
         }
         if hold_down {
-            move_count = apply_hold(target, hold_block, next_blocks);
+            move_count = apply_hold(target, hold_block, field, next_blocks);
+            if move_count == HOLD_USING {
+                self.applied_actions.push(ReplayAction::Hold);
+            }
         }
         move_count
     }
 
     /// ポーズ操作が行われたかどうかを返す.
+    /// 押された瞬間だけを見る.1回の描画の間に何回か更新しても、押し続けている間に切り替わり続けないように.
     fn is_pause_requested(&self) -> bool {
         let key_input = self.key_input.lock().unwrap();
-        key_input.is_press(&self.keys.pause)
+        key_input.is_down(&self.keys.pause)
+    }
+
+    /// 攻撃先の切り替え操作が行われたかどうかを返す.
+    fn is_target_change_requested(&self) -> bool {
+        let key_input = self.key_input.lock().unwrap();
+        key_input.is_down(&self.keys.change_target)
+    }
+
+    fn take_applied_actions(&mut self) -> Vec<ReplayAction> {
+        std::mem::take(&mut self.applied_actions)
     }
 }
 
@@ -219,29 +401,29 @@ impl ComputerController {
 }
 
 impl PlayController for ComputerController {
-    fn plan(&mut self, target_block_type: &BlockType, hold_block_type: &BlockType, field: &Field) {
+    fn plan(&mut self, target_block_type: &BlockType, hold_block_type: &BlockType, field: &Field, piece_set: &PieceSet) {
         // とりあえず、なるべく下に配置出来るような形で組む.
+        // ブロックを拡大している場合は、拡大後のマスの区切りに揃えて探す.
         let mut max_y = 0;
+        let scale = field.get_config().block_scale;
 
         for use_hold in [false, true].iter() {
             let block_type = if *use_hold {hold_block_type} else {target_block_type};
             for rotate_count in 0..4 {
-                let mut block_shape = block_datas::block_shape(*block_type);
+                let mut block_shape = block_datas::scale_block(&piece_set.shape(*block_type), scale);
                 for _ in 0..rotate_count {
                     block_shape = vector_util::rotate_vec_90_clockwise(&block_shape);
                 }
-                if let Some(row) = field.get_all_grid_data().get(0) {
-                    let width = row.len() as i32;
-                    for target_pos_x in 0..width {
-                        let now_position = Grid::new(target_pos_x, 0);
-                        let ghost_grid = field.get_ghost_position(&block_shape, &now_position);
-                        if ghost_grid.y > max_y {
-                            // プラン更新.
-                            self.target_pos_x = target_pos_x;
-                            self.rotate_count = rotate_count;
-                            self.use_hold = *use_hold;
-                            max_y = ghost_grid.y;
-                        }
+                let width = field.get_config().width as i32;
+                for target_pos_x in (0..width).step_by(scale) {
+                    let now_position = Grid::new(target_pos_x, scale as i32 - 1);
+                    let ghost_grid = field.get_ghost_position(&block_shape, &now_position);
+                    if ghost_grid.y > max_y {
+                        // プラン更新.
+                        self.target_pos_x = target_pos_x;
+                        self.rotate_count = rotate_count;
+                        self.use_hold = *use_hold;
+                        max_y = ghost_grid.y;
                     }
                 }
             }
@@ -259,9 +441,15 @@ impl PlayController for ComputerController {
         // planに応じて動かす.1回の移動で複数のコマンドが打たれないように注意.
         let mut move_count = 0;
         if self.use_hold {
-            move_count = apply_hold(target, hold_block, next_blocks);
+            move_count = apply_hold(target, hold_block, field, next_blocks);
             self.use_hold = false;
         }
+        else if self.rotate_count >= 2 {
+            // 2回分の回転は180度回転で済ませる.
+            target.rotate_180(field);
+            self.rotate_count -= 2;
+            move_count += 1;
+        }
         else if self.rotate_count > 0 {
             target.rotate(field);
             self.rotate_count -= 1;
@@ -294,21 +482,228 @@ impl PlayController for ComputerController {
     }
 }
 
+/// 横移動キーを押している時間から、今回リピートで移動する回数を返す.
+/// repeat_counterにはこれまでにリピートした回数を入れておく.
+/// ARRが0の場合は壁に当たるまで移動できるよう、充分大きい値を返す.
+fn calc_horizontal_repeat(handling: &HandlingSettings, press_time: Duration, since_spawn: Duration, repeat_counter: &mut u32) -> u32 {
+    let charge_time = if handling.keep_das_charge {press_time} else {press_time.min(since_spawn)};
+    let das = Duration::from_millis(handling.das_ms as u64);
+    if charge_time <= das {
+        return 0;
+    }
+    // DASカット中のリピートは捨てる.カットが明けたときにまとめて動かないように.
+    let is_das_cut = since_spawn < Duration::from_millis(handling.das_cut_ms as u64);
+    if handling.arr_ms == ARR_INSTANT {
+        return if is_das_cut {0} else {u32::MAX};
+    }
+    let total_count = ((charge_time - das).as_millis() / handling.arr_ms as u128) as u32;
+    let repeat_count = total_count.saturating_sub(*repeat_counter);
+    *repeat_counter = total_count;
+    if is_das_cut {0} else {repeat_count}
+}
 
-fn apply_hold(target: &mut ControlBlock, hold_block: &mut HoldBlock, next_blocks: &mut NextBlocks) -> i32 {
+/// ホールドできればホールドして[HOLD_USING]を返す.できなければ0.
+pub fn apply_hold(target: &mut ControlBlock, hold_block: &mut HoldBlock, field: &Field, next_blocks: &mut NextBlocks) -> i32 {
     if hold_block.can_hold() {
         let current_block_type = target.block_type;
         if let Some(held_block_type) = hold_block.hold(current_block_type) {
             if held_block_type != BlockType::None {
-                target.apply_block(held_block_type, block_datas::BLOCK_START_POSITION); // スタート位置にリセット
+                target.apply_block(held_block_type, field.get_config().spawn_position()); // スタート位置にリセット
             }
             else{
                 // 新しいブロックを生成
                 let next_block = next_blocks.next();
-                target.apply_block(next_block, block_datas::BLOCK_START_POSITION);
+                target.apply_block(next_block, field.get_config().spawn_position());
             }
         }
         return HOLD_USING;
     }
     0
-}
\ No newline at end of file
+}
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::*;
+    use crate::gameplay::{
+        block::{next_blocks::RandomizerType, rotation_system::RotationSystemType},
+        field::FieldConfig,
+    };
+    use rand::{SeedableRng, rngs::StdRng};
+
+    /// 操作のテストで使う、操作するブロックとフィールドの一式.
+    pub(crate) struct ControlFixture {
+        pub piece_set: Arc<PieceSet>,
+        pub field: Field,
+        pub target: ControlBlock,
+        pub hold_block: HoldBlock,
+        pub next_blocks: NextBlocks,
+    }
+
+    impl ControlFixture {
+        /// 3マスのブロックセットで、field_configのフィールドにTブロックを出した状態で作る.
+        pub fn new(field_config: FieldConfig) -> Self {
+            let piece_set = Arc::new(PieceSet::builtin().remove(0));
+            let field = Field::new(field_config);
+            let mut target = ControlBlock::new(RotationSystemType::Srs.create(), piece_set.clone(), 1);
+            target.apply_block(BlockType::T, field.get_config().spawn_position());
+            ControlFixture {
+                next_blocks: NextBlocks::new(RandomizerType::Bag.create(piece_set.block_types()), StdRng::seed_from_u64(0)),
+                piece_set,
+                field,
+                target,
+                hold_block: HoldBlock::new(),
+            }
+        }
+
+        /// controllerに1回操作させて、移動した数を返す.
+        pub fn control(&mut self, controller: &mut dyn PlayController) -> i32 {
+            controller.control(&mut self.target, &mut self.hold_block, &self.field, &mut self.next_blocks, 1000, 0)
+        }
+
+        /// controllerにブロックが出た時の操作をさせる.
+        pub fn spawn_control(&mut self, controller: &mut dyn PlayController) {
+            controller.spawn_control(&mut self.target, &mut self.hold_block, &self.field, &mut self.next_blocks);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::test_support::ControlFixture;
+    use crate::gameplay::{
+        block::rotation_system::Orientation,
+        field::FieldConfig,
+        game_clock::{self, TickClock},
+    };
+    use std::io;
+
+    /// 決まったキーが押されていることにするキー入力.
+    struct FakeKeyInput {
+        down: Vec<KeyType>,
+        press: Vec<KeyType>,
+    }
+
+    impl KeyInput for FakeKeyInput {
+        fn poll_input(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+        fn is_press(&self, key: &KeyType) -> bool {
+            self.press.contains(key)
+        }
+        fn is_down(&self, key: &KeyType) -> bool {
+            self.down.contains(key)
+        }
+        fn is_up(&self, _: &KeyType) -> bool {
+            false
+        }
+        fn keep_input(&mut self) {
+        }
+    }
+
+    fn millis(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_calc_horizontal_repeat() {
+        let mut handling = HandlingSettings::standard();
+        handling.das_ms = 100;
+        handling.arr_ms = 20;
+        let mut counter = 0;
+        assert_eq!(calc_horizontal_repeat(&handling, millis(100), millis(1000), &mut counter), 0);
+        assert_eq!(calc_horizontal_repeat(&handling, millis(160), millis(1000), &mut counter), 3);
+        assert_eq!(calc_horizontal_repeat(&handling, millis(200), millis(1000), &mut counter), 2);
+
+        // 溜めを持ち越さない場合は、ブロックが出てからの時間しか溜まらない.
+        handling.keep_das_charge = false;
+        let mut counter = 0;
+        assert_eq!(calc_horizontal_repeat(&handling, millis(1000), millis(50), &mut counter), 0);
+        assert_eq!(calc_horizontal_repeat(&handling, millis(1000), millis(140), &mut counter), 2);
+
+        // DASカット中は動かず、明けてもまとめて動かない.
+        handling.keep_das_charge = true;
+        handling.das_cut_ms = 100;
+        let mut counter = 0;
+        assert_eq!(calc_horizontal_repeat(&handling, millis(1000), millis(50), &mut counter), 0);
+        assert_eq!(calc_horizontal_repeat(&handling, millis(1020), millis(110), &mut counter), 1);
+
+        handling.arr_ms = ARR_INSTANT;
+        assert_eq!(calc_horizontal_repeat(&handling, millis(1000), millis(50), &mut counter), 0);
+        assert_eq!(calc_horizontal_repeat(&handling, millis(1000), millis(110), &mut counter), u32::MAX);
+    }
+
+    #[test]
+    fn test_press_time_on_game_clock() {
+        // キーを押している時間はインゲームの時計で測る.時計を進めない間はDASが溜まらない.
+        let key_input = Arc::new(Mutex::new(FakeKeyInput {
+            down: vec![],
+            press: vec![KeyType::P1Left],
+        }));
+        let clock = Arc::new(TickClock::new());
+        let mut handling = HandlingSettings::standard();
+        handling.das_cut_ms = 0;
+        let das_ms = handling.das_ms;
+        let mut controller = PlayerController::new(PlayerKeyAssigns::player1_keys(), handling, key_input.clone(), clock.clone());
+        let mut fixture = ControlFixture::new(FieldConfig::wide());
+        controller.plan(&BlockType::T, &BlockType::None, &fixture.field, &fixture.piece_set);
+        for _ in 0..10 {
+            controller.begin_frame(0);
+            assert_eq!(fixture.control(&mut controller), 0);
+        }
+        // 時計を進めると、DASを越えたところでリピートが始まる.
+        let das_ticks = das_ms as u64 * game_clock::TICKS_PER_SECOND / 1000;
+        let mut first_move_tick = None;
+        for tick in 1..=das_ticks + game_clock::TICKS_PER_SECOND {
+            clock.advance();
+            controller.begin_frame(0);
+            if fixture.control(&mut controller) > 0 {
+                first_move_tick = Some(tick);
+                break;
+            }
+        }
+        assert!(first_move_tick.is_some_and(|tick| tick > das_ticks));
+        // キーを離すと押し始めた時刻は忘れる.
+        key_input.lock().unwrap().press.clear();
+        controller.begin_frame(0);
+        assert_eq!(controller.left_pressed_at, None);
+    }
+
+    #[test]
+    fn test_pause_on_press_edge() {
+        // 押された瞬間だけポーズを要求し、押し続けている間は要求しない.
+        let key_input = Arc::new(Mutex::new(FakeKeyInput {
+            down: vec![KeyType::P1Pause],
+            press: vec![KeyType::P1Pause],
+        }));
+        let controller = PlayerController::new(PlayerKeyAssigns::player1_keys(), HandlingSettings::standard(), key_input.clone(), Arc::new(TickClock::new()));
+        assert!(controller.is_pause_requested());
+        key_input.lock().unwrap().down.clear();
+        assert!(!controller.is_pause_requested());
+    }
+
+    #[test]
+    fn test_buffered_input() {
+        let key_input = Arc::new(Mutex::new(FakeKeyInput {
+            down: vec![KeyType::P1Hold, KeyType::P1Rotate, KeyType::P1Left],
+            press: vec![],
+        }));
+        let mut controller = PlayerController::new(PlayerKeyAssigns::player1_keys(), HandlingSettings::standard(), key_input.clone(), Arc::new(TickClock::new()));
+        let mut fixture = ControlFixture::new(FieldConfig::standard());
+
+        // ライン消去中に押されたキーを覚えておく.
+        controller.buffer_input();
+        key_input.lock().unwrap().down.clear();
+        fixture.spawn_control(&mut controller);
+        assert_eq!(fixture.hold_block.get_holding_block(), BlockType::T);
+        assert_eq!(fixture.target.orientation, Orientation::Right);
+
+        // 横移動は最初の操作フレームで行う.
+        let spawn_x = fixture.target.position.x;
+        let move_count = fixture.control(&mut controller);
+        assert_eq!(move_count, 1);
+        assert_eq!(fixture.target.position.x, spawn_x - 1);
+        // 行った操作を順に記録している.
+        assert_eq!(controller.take_applied_actions(), vec![ReplayAction::Hold, ReplayAction::Rotate, ReplayAction::Left]);
+        assert!(controller.take_applied_actions().is_empty());
+    }
+}
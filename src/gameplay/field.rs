@@ -1,67 +1,273 @@
 //! ブロックが配置されるフィールドを定義.
 
-use crate::gameplay::block::block_datas::{self, BlockType};
-use crate::utility::{
-    grid::Grid,
-    vector_util,
-};
-use rand::{self, Rng};
-pub const FIELD_WIDTH: usize = 7;
-pub const FIELD_HEIGHT_WITH_OUTSIDE: usize = block_datas::BLOCK_START_POSITION_Y as usize * 2;
+use crate::gameplay::{block::block_datas::{self, BlockType}, gameplay_event::GameOverReason};
+use crate::utility::grid::Grid;
+use rand::{Rng, rngs::StdRng};
+use std::time::Instant;
+
+/// 出現するブロックの幅.出現位置を中央に寄せるために使う.
+const SPAWN_BLOCK_WIDTH: usize = 3;
+/// ビッグモードのブロックの拡大率.
+const BIG_BLOCK_SCALE: usize = 2;
+/// ビッグモードで最低限必要な列数(拡大後のマス単位).Iブロックを横に置けるようにする.
+const BIG_MIN_COLUMNS: usize = 4;
+
+/// フィールドの大きさの設定.
+/// 見えている部分の上に、ブロックが出現する見えない部分(バッファ)がある.
+/// ブロックの拡大率が2以上なら、ブロックの1マスはフィールドのblock_scale x block_scaleマスを占める.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FieldConfig {
+    pub width: usize,
+    pub visible_height: usize,
+    pub buffer_height: usize,
+    pub block_scale: usize,
+}
+
+impl FieldConfig {
+    /// 新規インスタンス作成.ブロックは拡大しない.
+    pub fn new(width: usize, visible_height: usize, buffer_height: usize) -> Self {
+        FieldConfig { width, visible_height, buffer_height, block_scale: 1 }
+    }
+
+    /// ビッグモード用の設定にする.ブロックは2x2マスになり、移動もライン消去も2マス単位になる.
+    /// 拡大したブロックがはみ出さないよう、幅と高さは2の倍数に切り上げ、幅は最低限の列数を確保する.
+    pub fn big(self) -> Self {
+        let round_up = |length: usize| length.div_ceil(BIG_BLOCK_SCALE) * BIG_BLOCK_SCALE;
+        FieldConfig {
+            width: round_up(self.width).max(BIG_MIN_COLUMNS * BIG_BLOCK_SCALE),
+            visible_height: round_up(self.visible_height),
+            buffer_height: round_up(self.buffer_height),
+            block_scale: BIG_BLOCK_SCALE,
+        }
+    }
+
+    /// 標準のフィールド.7x17.
+    pub fn standard() -> Self {
+        FieldConfig::new(7, 17, 17)
+    }
+
+    /// 広いフィールド.10x20.
+    pub fn wide() -> Self {
+        FieldConfig::new(10, 20, 20)
+    }
+
+    /// コンボ練習用の細いフィールド.4x20.
+    pub fn narrow() -> Self {
+        FieldConfig::new(4, 20, 20)
+    }
+
+    /// 練習用の高いフィールド.10x30.
+    pub fn tall() -> Self {
+        FieldConfig::new(10, 30, 20)
+    }
+
+    /// バッファを含めたフィールドの高さ.
+    pub fn total_height(&self) -> usize {
+        self.visible_height + self.buffer_height
+    }
+
+    /// ブロックを操作可能にする場合に、配置される場所.
+    /// 横方向は中央、縦方向は見えている部分の一番上にブロックの下端が来る.
+    /// ブロックを拡大する場合は、拡大後のマスの区切りに揃える.
+    pub fn spawn_position(&self) -> Grid {
+        let scale = self.block_scale;
+        let columns = self.width / scale;
+        Grid::new((columns.saturating_sub(SPAWN_BLOCK_WIDTH) / 2 * scale) as i32, (self.buffer_height + scale - 1) as i32)
+    }
+}
+
+/// ライン消去後に、上に残ったマスの落とし方.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GravityType {
+    /// 空になった行を詰めるだけ.マスは浮いたままになることがある.
+    Naive,
+    /// つながったマスの塊ごとに、着地するまで落とす.
+    Sticky,
+    /// マスを1つずつ、着地するまで落とす.
+    Cascade,
+}
 
 /// ブロックが配置されるフィールドの構造体.
+/// 各マスが固定された時刻も持っておき、スタックの見せ方に使う.
 #[derive(Debug)]
 pub struct Field{
-    grid_data: [[BlockType; FIELD_WIDTH]; FIELD_HEIGHT_WITH_OUTSIDE],
-    force_gameover: bool
+    config: FieldConfig,
+    grid_data: Vec<Vec<BlockType>>,
+    lock_times: Vec<Vec<Option<Instant>>>,
+    force_gameover: Option<GameOverReason>,
 }
 
 impl Field{
     /// 新規インスタンス作成.最初はからっぽ.
-    pub fn new() -> Self {
+    pub fn new(config: FieldConfig) -> Self {
         Field {
-            grid_data: [[BlockType::None; FIELD_WIDTH]; FIELD_HEIGHT_WITH_OUTSIDE],
-            force_gameover: false,
+            config,
+            grid_data: vec![vec![BlockType::None; config.width]; config.total_height()],
+            lock_times: vec![vec![None; config.width]; config.total_height()],
+            force_gameover: None,
         }
     }
+
+    /// フィールドの設定を返す.
+    pub fn get_config(&self) -> &FieldConfig {
+        &self.config
+    }
+
+    /// 空のラインを作る.
+    fn empty_line(&self) -> Vec<BlockType> {
+        vec![BlockType::None; self.config.width]
+    }
     /// 全て埋まった行を消す.消した行数を返す.
+    /// ブロックを拡大している場合は行が拡大率ごとにまとめて埋まるので、その組の数を返す.
     pub fn clear_lines(&mut self) -> u32 {
         let mut cleared_lines = 0;
-        for y in (0..FIELD_HEIGHT_WITH_OUTSIDE).rev() {
+        for y in (0..self.config.total_height()).rev() {
             if self.grid_data[y].iter().all(|&block| block != BlockType::None) {
-                self.grid_data[y] = [BlockType::None; FIELD_WIDTH];
+                self.grid_data[y] = self.empty_line();
+                self.lock_times[y] = vec![None; self.config.width];
                 cleared_lines += 1;
             }
         }
-        cleared_lines
+        cleared_lines / self.config.block_scale as u32
     }
 
     /// 全て空のラインを走査して、下に落として空のラインを埋める.
     pub fn drop_lines(&mut self) {
-        for y in (1..FIELD_HEIGHT_WITH_OUTSIDE).rev() {
+        for y in (1..self.config.total_height()).rev() {
             if self.grid_data[y].iter().all(|&block| block == BlockType::None) {
                 for pull_y in (1..=y).rev() {
-                    self.grid_data[pull_y] = self.grid_data[pull_y - 1];
+                    self.grid_data[pull_y] = self.grid_data[pull_y - 1].clone();
+                    self.lock_times[pull_y] = self.lock_times[pull_y - 1].clone();
                 }
-                self.grid_data[0] = [BlockType::None; FIELD_WIDTH];
+                self.grid_data[0] = self.empty_line();
+                self.lock_times[0] = vec![None; self.config.width];
+            }
+        }
+    }
+
+    /// 重力の種類に応じて、ライン消去後のマスを落とす.
+    /// 落とした結果、新たに揃った行があれば連鎖になる.
+    pub fn apply_gravity(&mut self, gravity_type: GravityType) {
+        match gravity_type {
+            GravityType::Naive => self.drop_lines(),
+            GravityType::Sticky => self.drop_sticky(),
+            GravityType::Cascade => self.drop_cascade(),
+        }
+    }
+
+    /// つながったマスの塊ごとに、これ以上落ちなくなるまで1段ずつ落とす.
+    /// ブロックを拡大している場合は拡大率の行数ずつ落として、拡大したマスの区切りを崩さない.
+    fn drop_sticky(&mut self) {
+        let step = self.config.block_scale;
+        loop {
+            let mut groups = self.find_groups();
+            // 下にある塊から落とす.
+            groups.sort_by_key(|group| std::cmp::Reverse(group.iter().map(|(_, y)| *y).max()));
+            let mut moved = false;
+            for group in groups {
+                let can_fall = group.iter().all(|&(x, y)| {
+                    y + step < self.config.total_height()
+                        && (self.grid_data[y + step][x] == BlockType::None || group.contains(&(x, y + step)))
+                });
+                if !can_fall {
+                    continue;
+                }
+                // 下のマスから動かさないと上書きしてしまう.
+                let mut cells = group;
+                cells.sort_by_key(|(_, y)| std::cmp::Reverse(*y));
+                for (x, y) in cells {
+                    self.grid_data[y + step][x] = self.grid_data[y][x];
+                    self.lock_times[y + step][x] = self.lock_times[y][x];
+                    self.grid_data[y][x] = BlockType::None;
+                    self.lock_times[y][x] = None;
+                }
+                moved = true;
+            }
+            if !moved {
+                break;
+            }
+        }
+    }
+
+    /// 上下左右につながったマスの塊を探す.
+    fn find_groups(&self) -> Vec<Vec<(usize, usize)>> {
+        let width = self.config.width;
+        let height = self.config.total_height();
+        let mut visited = vec![vec![false; width]; height];
+        let mut groups = vec![];
+        for start_y in 0..height {
+            for start_x in 0..width {
+                if visited[start_y][start_x] || self.grid_data[start_y][start_x] == BlockType::None {
+                    continue;
+                }
+                let mut group = vec![];
+                let mut stack = vec![(start_x, start_y)];
+                visited[start_y][start_x] = true;
+                while let Some((x, y)) = stack.pop() {
+                    group.push((x, y));
+                    let neighbors = [
+                        (x.wrapping_sub(1), y),
+                        (x + 1, y),
+                        (x, y.wrapping_sub(1)),
+                        (x, y + 1),
+                    ];
+                    for (next_x, next_y) in neighbors {
+                        if next_x < width && next_y < height && !visited[next_y][next_x] && self.grid_data[next_y][next_x] != BlockType::None {
+                            visited[next_y][next_x] = true;
+                            stack.push((next_x, next_y));
+                        }
+                    }
+                }
+                groups.push(group);
+            }
+        }
+        groups
+    }
+
+    /// 列ごとに、マスを1つずつ下に詰める.
+    /// ブロックを拡大している場合は底から拡大率の行数ずつの組で詰めて、拡大したマスの区切りを崩さない.
+    fn drop_cascade(&mut self) {
+        let height = self.config.total_height();
+        let rows: Vec<usize> = (0..height).rev().collect();
+        for x in 0..self.config.width {
+            let chunks: Vec<Vec<(BlockType, Option<Instant>)>> = rows.chunks(self.config.block_scale)
+                .filter(|chunk| chunk.iter().any(|&y| self.grid_data[y][x] != BlockType::None))
+                .map(|chunk| chunk.iter().map(|&y| (self.grid_data[y][x], self.lock_times[y][x])).collect())
+                .collect();
+            for y in 0..height {
+                self.grid_data[y][x] = BlockType::None;
+                self.lock_times[y][x] = None;
+            }
+            for (&y, (block, lock_time)) in rows.iter().zip(chunks.into_iter().flatten()) {
+                self.grid_data[y][x] = block;
+                self.lock_times[y][x] = lock_time;
             }
         }
     }
 
     /// フィールドがいっぱいかどうかを返す.
-    pub fn check_game_over(&self, next_block: &BlockType) -> bool {
+    /// spawn_offsetは次のブロックの出現位置のずれ.
+    pub fn check_game_over(&self, next_block_shape: &Vec<Vec<BlockType>>, spawn_offset: &Grid) -> bool {
         // 他の要因でゲームオーバー扱いになっている.
-        if self.force_gameover {
+        if self.force_gameover.is_some() {
             return true;
         }
         // 初期配置のブロックがすでに配置されたブロックとぶつかっていたらゲームオーバー.
-        let block_shape = block_datas::block_shape(*next_block);
         // ブロックの下部には空白があり得るが、初期配置の際にはその分を埋める.
-        let padding = block_shape.len() - 1 - block_datas::calc_block_bottom(&block_shape);
-        let start_pos = Grid::new(block_datas::BLOCK_START_POSITION.x, block_datas::BLOCK_START_POSITION.y + padding as i32);
-        self.check_collision(&block_shape, &start_pos)
+        let scale = self.config.block_scale;
+        let next_block_shape = &block_datas::scale_block(next_block_shape, scale);
+        let padding = next_block_shape.len() - 1 - block_datas::calc_block_bottom(next_block_shape);
+        let spawn_offset = Grid::new(spawn_offset.x * scale as i32, spawn_offset.y * scale as i32);
+        let spawn_position = self.config.spawn_position() + &spawn_offset;
+        let start_pos = Grid::new(spawn_position.x, spawn_position.y + padding as i32);
+        self.check_collision(next_block_shape, &start_pos)
     }
     
+    /// 固定や押し上げでゲームオーバー扱いになっていれば、その理由を返す.
+    pub fn get_forced_game_over(&self) -> Option<GameOverReason> {
+        self.force_gameover
+    }
+
     /// ブロックがフィールドと衝突するかどうかを返す.
     /// positionはblock_shapeの一番左下の座標.
     pub fn check_collision(&self, block_shape: &Vec<Vec<BlockType>>, position: &Grid) -> bool {
@@ -72,10 +278,7 @@ impl Field{
                     let grid_x: i32 = position.x + x as i32;
                     let grid_y: i32 = pos_y_upper + y as i32;
                     let grid = Grid { x: grid_x, y: grid_y };
-                    if !self.check_position_in_field(&grid) {
-                        return true;
-                    }
-                    else if self.grid_data[grid_y as usize][grid_x as usize] != BlockType::None {
+                    if !self.check_position_in_field(&grid) || self.grid_data[grid_y as usize][grid_x as usize] != BlockType::None {
                         return true;
                     }
                 }
@@ -85,24 +288,25 @@ impl Field{
     }
     
     /// ブロックの配置予測を出す.
-    /// 返ってくる位置はblock_shapeの一番左下の座標.
+    /// 返ってくる位置はblock_shapeの一番左下の座標.ブロックと同じく拡大率の単位で落とす.
     pub fn get_ghost_position(&self, block_shape: &Vec<Vec<BlockType>>, now_position: &Grid) -> Grid {
-        if block_shape.iter().map(|line| line.iter().all(|cell| *cell == BlockType::None)).all(|b| b) {
+        if block_shape.iter().all(|line| line.iter().all(|cell| *cell == BlockType::None)) {
             // 全部Noneだったら判定出来ないので、とりあえずもらった値をそのまま返す.
             return now_position.clone();
         }
+        let step = self.config.block_scale as i32;
         let mut ghost_position = now_position.clone();
-        let mut next_ghost_position = Grid {x: now_position.x, y: now_position.y + 1};
+        let mut next_ghost_position = Grid {x: now_position.x, y: now_position.y + step};
         while !self.check_collision(block_shape, &next_ghost_position) {
             ghost_position = next_ghost_position;
-            next_ghost_position = Grid { x:ghost_position.x, y: ghost_position.y + 1};
+            next_ghost_position = Grid { x:ghost_position.x, y: ghost_position.y + step};
         }
         ghost_position
     }
     
     /// フィールドにブロックを固定する.
-    /// positionはblock_shapeの一番左下の座標.
-    pub fn lock_block(&mut self, block_shape: &Vec<Vec<BlockType>>, position: &Grid) {
+    /// positionはblock_shapeの一番左下の座標.固定した時刻としてnowを記録する.
+    pub fn lock_block(&mut self, block_shape: &Vec<Vec<BlockType>>, position: &Grid, now: Instant) {
         let pos_y_upper = position.y + 1 - block_shape.len() as i32;
         let mut put_in_field = false;
         for y in 0..block_shape.len() {
@@ -112,6 +316,7 @@ impl Field{
                     let grid_y: i32 = pos_y_upper + y as i32;
                     if self.check_position_in_field(&Grid{ x: grid_x, y: grid_y }) {
                         self.grid_data[grid_y as usize][grid_x as usize] = block_shape[y][x];
+                        self.lock_times[grid_y as usize][grid_x as usize] = Some(now);
                         put_in_field = true;
                     }
                 }
@@ -119,41 +324,63 @@ impl Field{
         }
         if !put_in_field {
             // ブロックが完全にフィールド外に配置された場合はゲームオーバー.
-            self.force_gameover = true;
+            self.force_gameover = Some(GameOverReason::LockedOutside);
         }
     }
     
     /// 攻撃を受け入れて下部にラインを増やす.
-    pub fn apply_attack(&mut self, up_lines: usize) {
+    /// 穴の位置は渡された乱数で決める.せり上がったブロックはnowに固定されたものとする.
+    /// ブロックを拡大している場合は、1ラインを拡大率の数の行として、穴も拡大率の幅で空ける.
+    pub fn apply_attack(&mut self, up_lines: usize, rng: &mut StdRng, now: Instant) {
         // 押し上げて…
-        for y in 0..FIELD_HEIGHT_WITH_OUTSIDE {
-            for x in 0..FIELD_WIDTH {
+        let scale = self.config.block_scale;
+        let up_lines = up_lines * scale;
+        let width = self.config.width;
+        let height = self.config.total_height();
+        for y in 0..height {
+            for x in 0..width {
                 if y < up_lines {
                     if self.grid_data[y][x] != BlockType::None {
                         // 押し上げで枠を越えたらゲームオーバー.
-                        self.force_gameover = true;
+                        self.force_gameover = Some(GameOverReason::PushedOver);
                     }
                     continue;
                 }
                 let to_y = y - up_lines;
                 self.grid_data[to_y][x] = self.grid_data[y][x];
+                self.lock_times[to_y][x] = self.lock_times[y][x];
             }
         }
         // お邪魔を配置.
-        let open_pos_x = rand::rng().random::<u32>() as usize % FIELD_WIDTH;
-        let put_start_y = FIELD_HEIGHT_WITH_OUTSIDE - up_lines;
-        for y in put_start_y..FIELD_HEIGHT_WITH_OUTSIDE {
-            for x in 0..FIELD_WIDTH {
-                if x == open_pos_x {
+        let open_column = rng.random_range(0..width / scale);
+        let put_start_y = height.saturating_sub(up_lines);
+        for y in put_start_y..height {
+            for x in 0..width {
+                if x / scale == open_column {
                     self.grid_data[y][x] = BlockType::None;
+                    self.lock_times[y][x] = None;
                 }
                 else{
                     self.grid_data[y][x] = BlockType::Attacked;
+                    self.lock_times[y][x] = Some(now);
                 }
             }
         }
     }
 
+    /// フィールドが空になっているかどうかを返す.
+    pub fn is_empty(&self) -> bool {
+        self.grid_data.iter().all(|line| line.iter().all(|&block| block == BlockType::None))
+    }
+
+    /// 積み上がっている高さを返す.一番上のブロックがある行から底までの行数.
+    pub fn calc_stack_height(&self) -> usize {
+        let total_height = self.config.total_height();
+        self.grid_data.iter()
+            .position(|line| line.iter().any(|&block| block != BlockType::None))
+            .map_or(0, |top| total_height - top)
+    }
+
     /// 指定した位置の状態を取得する.
     pub fn get_grid_data(&self, position: &Grid) -> BlockType {
         if self.check_position_in_field(position) {
@@ -163,15 +390,24 @@ impl Field{
         }
     }
     
+    /// 指定した位置のマスが固定された時刻を取得する.空のマスやフィールド外ならNone.
+    pub fn get_lock_time(&self, position: &Grid) -> Option<Instant> {
+        if self.check_position_in_field(position) {
+            self.lock_times[position.y as usize][position.x as usize]
+        } else {
+            None
+        }
+    }
+
     /// フィールド全体の状態を取得する.
     pub fn get_all_grid_data(&self) -> Vec<Vec<BlockType>> {
-        vector_util::array_to_vec_2d(self.grid_data)
+        self.grid_data.clone()
     }
     
     /// positionがフィールド内にあるかどうかを返す.
     pub fn check_position_in_field(&self, position: &Grid) -> bool {
-        position.x >= 0 && position.x < FIELD_WIDTH as i32 &&
-        position.y >= 0 && position.y < FIELD_HEIGHT_WITH_OUTSIDE as i32
+        position.x >= 0 && position.x < self.config.width as i32 &&
+        position.y >= 0 && position.y < self.config.total_height() as i32
     }
 }
 
@@ -181,9 +417,9 @@ mod tests {
 
     #[test]
     fn test_field_creation() {
-        let field = Field::new();
-        for y in 0..FIELD_HEIGHT_WITH_OUTSIDE {
-            for x in 0..FIELD_WIDTH {
+        let field = Field::new(FieldConfig::standard());
+        for y in 0..field.config.total_height() {
+            for x in 0..field.config.width {
                 assert_eq!(field.get_grid_data(&Grid { x: x as i32, y: y as i32 }), BlockType::None);
             }
         }
@@ -191,40 +427,201 @@ mod tests {
 
     #[test]
     fn test_lock_and_get_grid_data() {
-        let mut field = Field::new();
-        let block_shape = block_datas::block_shape(BlockType::I);
+        let mut field = Field::new(FieldConfig::standard());
+        let block_shape = vec![
+            vec![BlockType::None, BlockType::None, BlockType::None],
+            vec![BlockType::I, BlockType::I, BlockType::I],
+            vec![BlockType::None, BlockType::None, BlockType::None],
+        ];
         let position = Grid { x: 3, y: 2 };
-        field.lock_block(&block_shape, &position);
+        field.lock_block(&block_shape, &position, Instant::now());
         assert_eq!(field.get_grid_data(&Grid { x: 3, y: 1 }), BlockType::I);
         assert_eq!(field.get_grid_data(&Grid { x: 4, y: 1 }), BlockType::I);
         assert_eq!(field.get_grid_data(&Grid { x: 5, y: 1 }), BlockType::I);
     }
 
+    #[test]
+    fn test_lock_times() {
+        let mut field = Field::new(FieldConfig::standard());
+        let last_y = field.config.total_height() as i32 - 1;
+        let block_shape = vec![vec![BlockType::O, BlockType::O]];
+        field.lock_block(&block_shape, &Grid::new(0, last_y - 1), Instant::now());
+        assert!(field.get_lock_time(&Grid::new(0, last_y - 1)).is_some());
+        assert!(field.get_lock_time(&Grid::new(0, last_y)).is_none());
+        // 下の空いたラインを詰めると、時刻も一緒に落ちる.
+        field.drop_lines();
+        assert!(field.get_lock_time(&Grid::new(1, last_y)).is_some());
+        assert!(field.get_lock_time(&Grid::new(1, last_y - 1)).is_none());
+    }
+
     #[test]
     fn test_clear_lines() {
-        let mut field = Field::new();
-        let last_y = FIELD_HEIGHT_WITH_OUTSIDE - 1;
-        for x in 0..FIELD_WIDTH {
+        let mut field = Field::new(FieldConfig::standard());
+        let last_y = field.config.total_height() - 1;
+        for x in 0..field.config.width {
             field.grid_data[last_y][x] = BlockType::I;
         }
         let cleared_lines = field.clear_lines();
         assert_eq!(cleared_lines, 1);
-        for x in 0..FIELD_WIDTH {
+        for x in 0..field.config.width {
             assert_eq!(field.get_grid_data(&Grid { x: x as i32, y: last_y as i32 }), BlockType::None);
         }
     }
 
     #[test]
     fn test_drop_lines() {
-        let mut field = Field::new();
-        let last_y = FIELD_HEIGHT_WITH_OUTSIDE - 1;
-        for x in 0..FIELD_WIDTH {
+        let mut field = Field::new(FieldConfig::standard());
+        let last_y = field.config.total_height() - 1;
+        for x in 0..field.config.width {
             field.grid_data[last_y - 1][x] = BlockType::I;
         }
         field.drop_lines();
-        for x in 0..FIELD_WIDTH {
+        for x in 0..field.config.width {
             assert_eq!(field.get_grid_data(&Grid { x: x as i32, y: last_y as i32}), BlockType::I);
             assert_eq!(field.get_grid_data(&Grid { x: x as i32, y: last_y as i32 - 1 }), BlockType::None);
         }
     }
+
+    #[test]
+    fn test_sticky_gravity() {
+        let mut field = Field::new(FieldConfig::narrow());
+        let last_y = field.config.total_height() - 1;
+        // 底の1マスと、浮いているL字の塊.
+        field.grid_data[last_y][0] = BlockType::I;
+        field.grid_data[last_y - 3][1] = BlockType::L;
+        field.grid_data[last_y - 2][1] = BlockType::L;
+        field.grid_data[last_y - 2][2] = BlockType::L;
+        field.apply_gravity(GravityType::Sticky);
+        // 塊のまま、一番下のマスが底に着くまで落ちる.
+        assert_eq!(field.grid_data[last_y][1], BlockType::L);
+        assert_eq!(field.grid_data[last_y][2], BlockType::L);
+        assert_eq!(field.grid_data[last_y - 1][1], BlockType::L);
+        assert_eq!(field.grid_data[last_y][0], BlockType::I);
+        assert_eq!(field.grid_data[last_y - 2][1], BlockType::None);
+    }
+
+    #[test]
+    fn test_cascade_gravity() {
+        let mut field = Field::new(FieldConfig::narrow());
+        let last_y = field.config.total_height() - 1;
+        // 1マスずつ落ちると1列揃うように置く.
+        field.grid_data[last_y][0] = BlockType::I;
+        field.grid_data[last_y][1] = BlockType::I;
+        field.grid_data[last_y - 1][1] = BlockType::T;
+        field.grid_data[last_y - 2][2] = BlockType::T;
+        field.grid_data[last_y - 1][3] = BlockType::T;
+        field.grid_data[last_y - 2][3] = BlockType::T;
+        field.apply_gravity(GravityType::Cascade);
+        assert_eq!(field.grid_data[last_y - 1][1], BlockType::T);
+        assert_eq!(field.grid_data[last_y][3], BlockType::T);
+        assert_eq!(field.clear_lines(), 1);
+        // 空になった行を詰めるだけなら、下が空いていても浮いたまま.
+        let mut field = Field::new(FieldConfig::narrow());
+        field.grid_data[last_y][0] = BlockType::I;
+        field.grid_data[last_y - 2][2] = BlockType::T;
+        field.apply_gravity(GravityType::Naive);
+        assert_eq!(field.grid_data[last_y - 1][2], BlockType::T);
+        assert_eq!(field.grid_data[last_y][2], BlockType::None);
+    }
+
+    #[test]
+    fn test_field_config() {
+        let config = FieldConfig::standard();
+        assert_eq!(config.total_height(), 34);
+        assert_eq!(config.spawn_position(), Grid::new(2, 17));
+
+        let config = FieldConfig::wide();
+        assert_eq!(config.spawn_position(), Grid::new(3, 20));
+
+        let mut field = Field::new(FieldConfig::narrow());
+        let last_y = field.config.total_height() - 1;
+        for x in 0..4 {
+            field.grid_data[last_y][x] = BlockType::T;
+        }
+        assert_eq!(field.clear_lines(), 1);
+        assert!(!field.check_position_in_field(&Grid::new(4, 0)));
+    }
+
+    #[test]
+    fn test_calc_stack_height() {
+        let mut field = Field::new(FieldConfig::standard());
+        assert_eq!(field.calc_stack_height(), 0);
+        let total_height = field.config.total_height();
+        field.grid_data[total_height - 3][1] = BlockType::T;
+        field.grid_data[total_height - 1][0] = BlockType::T;
+        assert_eq!(field.calc_stack_height(), 3);
+        assert!(!field.is_empty());
+    }
+
+    #[test]
+    fn test_apply_attack() {
+        use rand::SeedableRng;
+        let mut field1 = Field::new(FieldConfig::standard());
+        let mut field2 = Field::new(FieldConfig::standard());
+        let mut rng1 = StdRng::seed_from_u64(42);
+        let mut rng2 = StdRng::seed_from_u64(42);
+        for _ in 0..5 {
+            field1.apply_attack(1, &mut rng1, Instant::now());
+            field2.apply_attack(1, &mut rng2, Instant::now());
+        }
+        // 同じシードなら同じ位置に穴が空く.
+        assert_eq!(field1.get_all_grid_data(), field2.get_all_grid_data());
+        let last_line = &field1.get_all_grid_data()[field1.config.total_height() - 1];
+        assert_eq!(last_line.iter().filter(|cell| **cell == BlockType::None).count(), 1);
+        assert_eq!(field1.get_forced_game_over(), None);
+        // 押し上げで枠を越えたらゲームオーバー.
+        field1.apply_attack(field1.config.total_height(), &mut rng1, Instant::now());
+        assert_eq!(field1.get_forced_game_over(), Some(GameOverReason::PushedOver));
+        assert!(field1.check_game_over(&vec![vec![BlockType::T]], &Grid::new(0, 0)));
+    }
+
+    #[test]
+    fn test_big_field() {
+        use rand::SeedableRng;
+        let config = FieldConfig::standard().big();
+        assert_eq!((config.width, config.visible_height, config.buffer_height), (8, 18, 18));
+        // 2マス単位の区切りに揃い、ブロックの下端は区切りの下の行に来る.
+        assert_eq!(config.spawn_position(), Grid::new(0, 19));
+        assert_eq!(FieldConfig::narrow().big().width, 8);
+
+        let mut field = Field::new(config);
+        let mut rng = StdRng::seed_from_u64(42);
+        field.apply_attack(1, &mut rng, Instant::now());
+        let grid_data = field.get_all_grid_data();
+        let height = config.total_height();
+        // 1ライン分の攻撃で2行せり上がり、穴は2マス幅で揃う.
+        assert_eq!(grid_data[height - 1], grid_data[height - 2]);
+        assert_eq!(grid_data[height - 1].iter().filter(|cell| **cell == BlockType::None).count(), 2);
+        assert!(grid_data[height - 3].iter().all(|cell| *cell == BlockType::None));
+
+        // 穴を埋めると2行消えて、1ラインとして数える.
+        for y in height - 2..height {
+            for x in 0..config.width {
+                field.grid_data[y][x] = BlockType::O;
+            }
+        }
+        assert_eq!(field.clear_lines(), 1);
+    }
+
+    #[test]
+    fn test_big_field_gravity() {
+        // 拡大したマスは、どの重力でも2行単位の区切りに揃ったまま落ちる.
+        for gravity_type in [GravityType::Sticky, GravityType::Cascade] {
+            let config = FieldConfig::standard().big();
+            let height = config.total_height();
+            let mut field = Field::new(config);
+            // 左に3マス積んだ柱と、その右上に斜めに接して浮いた1マス.どちらも2x2.
+            // 1行ずつ落とすと途中で柱とつながって、区切りからずれた位置で止まってしまう.
+            field.lock_block(&vec![vec![BlockType::I; 2]; 6], &Grid::new(0, height as i32 - 1), Instant::now());
+            field.lock_block(&vec![vec![BlockType::O; 2]; 2], &Grid::new(2, height as i32 - 7), Instant::now());
+            field.apply_gravity(gravity_type);
+            let grid_data = field.get_all_grid_data();
+            assert_eq!(grid_data[height - 8][2], BlockType::None);
+            assert_eq!(grid_data[height - 1][0], BlockType::I);
+            // 組になった行は同じ埋まり方.
+            for y in 0..height {
+                assert_eq!(grid_data[y], grid_data[y / 2 * 2], "{:?} {}", gravity_type, y);
+            }
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,67 @@
+//! ゲーム内の時計.
+//! インゲームの時間は実際の時刻ではなく、更新のたびに決まった時間だけ進める.
+//! 同じ操作なら同じ結果になり、実際の時間を待たずにいくらでも速く進められる.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// 1秒あたりの更新回数.描画のフレームレートとは関係なく、この間隔でゲームを進める.
+pub const TICKS_PER_SECOND: u64 = 60;
+/// 1回の更新で進む時間.
+pub const TICK_DURATION: Duration = Duration::from_nanos(1_000_000_000 / TICKS_PER_SECOND);
+
+/// ゲーム内の時刻を返すトレイト.
+pub trait GameClock {
+    /// 現在のゲーム内の時刻.
+    fn now(&self) -> Instant;
+    /// 1回の更新分だけ時刻を進める.
+    fn advance(&self);
+}
+
+/// 進めた回数だけ時刻が進む時計.
+pub struct TickClock {
+    start: Instant,
+    ticks: AtomicU64,
+}
+
+impl TickClock {
+    /// 新規インスタンス作成.作成した時刻から始まる.
+    pub fn new() -> Self {
+        TickClock {
+            start: Instant::now(),
+            ticks: AtomicU64::new(0),
+        }
+    }
+}
+
+impl GameClock for TickClock {
+    /// 始まりの時刻に、進めた回数分の時間を足した時刻.
+    /// 端数が積もらないよう、回数からまとめて計算する.
+    fn now(&self) -> Instant {
+        let ticks = self.ticks.load(Ordering::Relaxed);
+        self.start + Duration::from_nanos(ticks * 1_000_000_000 / TICKS_PER_SECOND)
+    }
+
+    fn advance(&self) {
+        self.ticks.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tick_clock() {
+        let clock = TickClock::new();
+        let start = clock.now();
+        // 進めなければ時刻は変わらない.
+        assert_eq!(clock.now(), start);
+        clock.advance();
+        assert_eq!(clock.now().duration_since(start), TICK_DURATION);
+        for _ in 1..TICKS_PER_SECOND {
+            clock.advance();
+        }
+        assert_eq!(clock.now().duration_since(start), Duration::from_secs(1));
+    }
+}
@@ -1,55 +1,168 @@
 //! ゲーム全体のマネージャー.
 use crate::gameplay::{
-    gameplay_manager::{GameplayManager, PlayerType},
+    block::{next_blocks::{Randomizer, RandomizerType}, piece_set::PieceSet, rotation_system::RotationSystemType},
+    field::{FieldConfig, GravityType},
+    game_clock::TickClock,
+    game_rules::GameRulesType,
+    gameplay_event::GameplayEvent,
+    gameplay_manager::{GameplayManager, GameplaySettings, PlayerType},
+    handling_settings::SOFT_DROP_INFINITE,
+    player_profile::{self, PlayerProfile},
+    replay::{Replay, ReplayController, ReplaySettings},
     game_renderer_sender::GameRendererSender,
     key_input::{KeyInput, KeyType},
+    score_calculator::{AttackTable, CompetitiveAttackPowerCalculator},
+    targeting::{Opponent, TargetingType},
+    visibility::VisibilityType,
 };
+use rand::{Rng, SeedableRng, rngs::StdRng};
 use std::sync::{Arc, Mutex};
 
 pub enum GameState {
     Title,
+    Options,
     Playing,
     Paused,
     GameOver,
+    Replay,
 }
 
+/// リプレイの再生速度.
+#[derive(PartialEq, Clone, Copy)]
+pub enum ReplaySpeed {
+    Normal,
+    Fast,
+    Step,
+}
+
+/// 早送りで1フレームに進める更新の回数.
+const REPLAY_FAST_UPDATES: usize = 4;
+
 #[derive(PartialEq)]
 pub enum TitleChoice {
     Play,
+    FieldSize,
+    RotationSystem,
+    PieceSet,
+    Randomizer,
+    Rules,
+    Big,
+    Visibility,
+    Gravity,
+    Options,
     Exit,
 }
 
+/// 設定画面で選べる項目.
+#[derive(PartialEq)]
+pub enum OptionsChoice {
+    Player,
+    Das,
+    Arr,
+    SoftDropFactor,
+    DasCut,
+    KeepDasCharge,
+    Back,
+}
+
+/// 設定画面で1回に変える時間.
+const OPTIONS_TIME_STEP_MS: u32 = 10;
+/// 設定画面で選べる時間の上限.
+const OPTIONS_TIME_MAX_MS: u32 = 1000;
+/// 設定画面で1回に変えるソフトドロップ倍率.
+const OPTIONS_SOFT_DROP_STEP: u32 = 5;
+/// 設定画面で選べるソフトドロップ倍率の上限.これを超えると一番下まで落ちるようになる.
+const OPTIONS_SOFT_DROP_MAX: u32 = 100;
+
 pub enum PlayStyle {
     Solo,
     WithNPC(usize),
     VSPlayer,
 }
 
+/// ゲームごとに選べるフィールドの大きさ.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum FieldSize {
+    Standard,
+    Wide,
+    Narrow,
+    Tall,
+}
+
+impl FieldSize {
+    /// 対応するフィールド設定を返す.
+    pub fn to_config(self) -> FieldConfig {
+        match self {
+            FieldSize::Standard => FieldConfig::standard(),
+            FieldSize::Wide => FieldConfig::wide(),
+            FieldSize::Narrow => FieldConfig::narrow(),
+            FieldSize::Tall => FieldConfig::tall(),
+        }
+    }
+}
+
 /// ゲーム全体を管理する構造体.
 /// 基本的にはシングルトンを想定している.
 pub struct GameManager {
     state: GameState,
     title_choice_command: TitleChoice,
     play_style: PlayStyle,
+    field_size: FieldSize,
+    rotation_system_type: RotationSystemType,
+    piece_sets: Vec<Arc<PieceSet>>,
+    piece_set_index: usize,
+    randomizer_type: RandomizerType,
+    rules_type: GameRulesType,
+    big_mode: bool,
+    visibility_type: VisibilityType,
+    gravity_type: GravityType,
+    profiles: Vec<PlayerProfile>,
+    options_choice: OptionsChoice,
+    options_player_index: usize,
+    options_message: Option<String>,
+    fixed_seed: Option<u64>,
+    seed: u64,
+    target_rng: StdRng,
     high_score: u64,
     level: u32,
     pub gameplay_managers: Vec<GameplayManager>,
     high_score_updated: bool,
+    replay_speed: ReplaySpeed,
+    replay_message: Option<String>,
     renderer_sender: Box<dyn GameRendererSender + Send>,
     key_input_manager: Arc<Mutex<dyn KeyInput + Send>>,
 }
 
 impl GameManager {
     /// 新規インスタンス作成.
+    /// ブロックセットは埋め込みのものを使う.定義ファイルから読み込んだものは[GameManager::set_piece_sets]で設定する.
     pub fn new(renderer_sender: Box<dyn GameRendererSender + Send>, key_input_manager: Arc<Mutex<dyn KeyInput + Send>>) -> Self {
         GameManager {
             state: GameState::Title,
             title_choice_command: TitleChoice::Play,
             play_style: PlayStyle::Solo,
+            field_size: FieldSize::Standard,
+            rotation_system_type: RotationSystemType::Srs,
+            piece_sets: PieceSet::builtin().into_iter().map(Arc::new).collect(),
+            piece_set_index: 0,
+            randomizer_type: RandomizerType::Bag,
+            rules_type: GameRulesType::Standard,
+            big_mode: false,
+            visibility_type: VisibilityType::Normal,
+            gravity_type: GravityType::Naive,
+            profiles: player_profile::load_profiles(),
+            options_choice: OptionsChoice::Player,
+            options_player_index: 0,
+            options_message: None,
+            fixed_seed: None,
+            seed: 0,
+            target_rng: StdRng::seed_from_u64(0),
             high_score: 0,
             level: 1,
             gameplay_managers: vec![],
             high_score_updated: false,
+            replay_speed: ReplaySpeed::Normal,
+            replay_message: None,
             renderer_sender,
             key_input_manager: key_input_manager,
         }
@@ -67,6 +180,76 @@ impl GameManager {
         &self.play_style
     }
 
+    pub fn get_field_size(&self) -> FieldSize {
+        self.field_size
+    }
+
+    pub fn get_rotation_system_type(&self) -> RotationSystemType {
+        self.rotation_system_type
+    }
+
+    /// 選択中のブロックセットを返す.
+    pub fn get_piece_set(&self) -> &PieceSet {
+        &self.piece_sets[self.piece_set_index]
+    }
+
+    pub fn get_randomizer_type(&self) -> RandomizerType {
+        self.randomizer_type
+    }
+
+    pub fn get_rules_type(&self) -> GameRulesType {
+        self.rules_type
+    }
+
+    /// ブロックを2x2マスにするビッグモードかどうか.
+    pub fn is_big_mode(&self) -> bool {
+        self.big_mode
+    }
+
+    pub fn get_visibility_type(&self) -> VisibilityType {
+        self.visibility_type
+    }
+
+    pub fn get_gravity_type(&self) -> GravityType {
+        self.gravity_type
+    }
+
+    pub fn get_options_choice(&self) -> &OptionsChoice {
+        &self.options_choice
+    }
+
+    /// 設定画面で編集中のプレイヤーの番号.
+    pub fn get_options_player_index(&self) -> usize {
+        self.options_player_index
+    }
+
+    /// 設定画面で編集中のプロフィール.
+    pub fn get_options_profile(&self) -> &PlayerProfile {
+        &self.profiles[self.options_player_index]
+    }
+
+    /// 設定画面に表示するメッセージ.保存に失敗した場合など.
+    pub fn get_options_message(&self) -> Option<&str> {
+        self.options_message.as_deref()
+    }
+
+    /// 起動時に指定されたシードを設定する.
+    /// 指定されていればゲームごとにランダムなシードを使わず、毎回同じシードで遊ぶ.
+    pub fn set_fixed_seed(&mut self, fixed_seed: Option<u64>) {
+        self.fixed_seed = fixed_seed;
+    }
+
+    /// 選べるブロックセットを設定する.選択は最初のブロックセットに戻す.
+    pub fn set_piece_sets(&mut self, piece_sets: Vec<PieceSet>) {
+        self.piece_sets = piece_sets.into_iter().map(Arc::new).collect();
+        self.piece_set_index = 0;
+    }
+
+    /// 現在(直前)のゲームのシードを返す.
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
     pub fn get_high_score_updated(&self) -> bool {
         self.high_score_updated
     }
@@ -75,19 +258,157 @@ impl GameManager {
         self.high_score
     }
 
+    pub fn get_replay_speed(&self) -> ReplaySpeed {
+        self.replay_speed
+    }
+
+    /// ゲームオーバー画面に表示する、リプレイを保存した場所か保存できなかった理由.
+    pub fn get_replay_message(&self) -> Option<&str> {
+        self.replay_message.as_deref()
+    }
+
+    /// リプレイの再生を始める.設定は記録されたものを使い、タイトルで選んだ設定は変えない.
+    /// 記録されたブロックセットが読み込まれていなければ再生できない.
+    pub fn start_replay(&mut self, replay: Replay) -> Result<(), String> {
+        let settings = replay.settings;
+        let piece_set = self.piece_sets.iter()
+            .find(|piece_set| piece_set.get_name() == settings.piece_set)
+            .cloned()
+            .ok_or(format!("ブロックセットが見つかりません: {}", settings.piece_set))?;
+        let field_config = settings.field_size.to_config();
+        let field_config = if settings.big {field_config.big()} else {field_config};
+        let gameplay_settings = GameplaySettings {
+            level: settings.level,
+            field_config,
+            rules: settings.rules.to_rules(),
+            rotation_system: settings.rotation_system.create(),
+            randomizer: settings.randomizer.create(piece_set.block_types()),
+            piece_set,
+            seed: settings.seed,
+        };
+        let mut gameplay_manager = GameplayManager::new(gameplay_settings, Box::new(ReplayController::new(replay.frames)), Arc::new(TickClock::new()));
+        gameplay_manager.set_visibility(settings.visibility.create());
+        gameplay_manager.set_gravity_type(settings.gravity);
+        self.gameplay_managers = vec![gameplay_manager];
+        self.seed = settings.seed;
+        self.replay_speed = ReplaySpeed::Normal;
+        self.state = GameState::Replay;
+        Ok(())
+    }
+
+    /// 1Pのゲームをリプレイとして保存する.結果はゲームオーバー画面に表示する.
+    fn save_replay(&mut self) {
+        let settings = ReplaySettings {
+            seed: self.seed,
+            level: self.level,
+            field_size: self.field_size,
+            big: self.big_mode,
+            rotation_system: self.rotation_system_type,
+            piece_set: self.get_piece_set().get_name().to_string(),
+            randomizer: self.randomizer_type,
+            rules: self.rules_type,
+            visibility: self.visibility_type,
+            gravity: self.gravity_type,
+            controller: PlayerType::Player1,
+        };
+        let replay = Replay::new(settings, self.gameplay_managers[0].get_replay_frames().to_vec());
+        self.replay_message = Some(match replay.save() {
+            Ok(path) => format!("リプレイ保存：{}", path.display()),
+            Err(message) => format!("リプレイを保存できません：{}", message),
+        });
+    }
+
     /// 有人プレイヤーでインゲームを作成する.
+    /// 操作感はそのプレイヤーのプロフィールの設定を使う.
+    /// インゲームごとに時計を分けて、更新した回数だけ時間が進むようにする.ポーズ中は進まない.
     pub fn create_player(&mut self, player_type: PlayerType) {
-        self.gameplay_managers.push(GameplayManager::with_player_controller(self.level, player_type, self.key_input_manager.clone()));
+        let handling = match player_type {
+            PlayerType::Player2 => self.profiles[1].handling,
+            _ => self.profiles[0].handling,
+        };
+        self.gameplay_managers.push(GameplayManager::with_player_controller(self.create_gameplay_settings(), player_type, handling, self.key_input_manager.clone(), Arc::new(TickClock::new())));
     }
 
     /// npcプレイヤーでインゲームを作成する.
     pub fn create_npc(&mut self) {
-        self.gameplay_managers.push(GameplayManager::with_npc_controller(self.level));
+        self.gameplay_managers.push(GameplayManager::with_npc_controller(self.create_gameplay_settings(), Arc::new(TickClock::new())));
+    }
+
+    /// タイトルで選んだ設定で、インゲームの設定を作る.
+    fn create_gameplay_settings(&self) -> GameplaySettings {
+        GameplaySettings {
+            level: self.level,
+            field_config: self.create_field_config(),
+            rules: self.rules_type.to_rules(),
+            rotation_system: self.rotation_system_type.create(),
+            piece_set: self.piece_sets[self.piece_set_index].clone(),
+            randomizer: self.create_randomizer(),
+            seed: self.seed,
+        }
+    }
+
+    /// 選択中のフィールドの大きさで、フィールド設定を作る.ビッグモードならビッグ用にする.
+    fn create_field_config(&self) -> FieldConfig {
+        let config = self.field_size.to_config();
+        if self.big_mode {config.big()} else {config}
+    }
+
+    /// 選択中のブロックセットとブロックの出し方で、ブロックの出し方を作る.
+    fn create_randomizer(&self) -> Box<dyn Randomizer + Send> {
+        self.randomizer_type.create(self.get_piece_set().block_types())
     }
 
-    /// 更新処理.
-    pub fn update(&mut self) -> bool{
-        let _ = self.key_input_manager.lock().unwrap().poll_input();
+    /// 設定画面で選んでいる項目の値を変える.forwardがtrueなら増やす.
+    fn adjust_option(&mut self, forward: bool) {
+        if self.options_choice == OptionsChoice::Player {
+            self.options_player_index = (self.options_player_index + 1) % self.profiles.len();
+            return;
+        }
+        let handling = &mut self.profiles[self.options_player_index].handling;
+        match self.options_choice {
+            OptionsChoice::Das => handling.das_ms = step_time(handling.das_ms, forward),
+            OptionsChoice::Arr => handling.arr_ms = step_time(handling.arr_ms, forward),
+            OptionsChoice::SoftDropFactor => handling.soft_drop_factor = step_soft_drop_factor(handling.soft_drop_factor, forward),
+            OptionsChoice::DasCut => handling.das_cut_ms = step_time(handling.das_cut_ms, forward),
+            OptionsChoice::KeepDasCharge => handling.keep_das_charge = !handling.keep_das_charge,
+            OptionsChoice::Player | OptionsChoice::Back => {},
+        }
+    }
+
+    /// 各プレイヤーの攻撃先を、選び方に応じて決め直す.
+    /// ゲームオーバーになった相手は狙わない.
+    fn update_targets(&mut self) {
+        for i in 0..self.gameplay_managers.len() {
+            if self.gameplay_managers[i].is_game_over() {
+                continue;
+            }
+            let opponents: Vec<Opponent> = self.gameplay_managers.iter().enumerate()
+                .filter(|(j, gameplay_manager)| i != *j && !gameplay_manager.is_game_over())
+                .map(|(j, gameplay_manager)| Opponent {
+                    index: j,
+                    badges: gameplay_manager.get_badges(),
+                    stack_height: gameplay_manager.get_stack_height(),
+                    is_attacking_me: self.gameplay_managers[i].is_attacked_by(j),
+                })
+                .collect();
+            let gameplay_manager = &self.gameplay_managers[i];
+            let targets = gameplay_manager.get_targeting_type().select_targets(&opponents, gameplay_manager.get_targets(), &mut self.target_rng);
+            self.gameplay_managers[i].set_targets(targets);
+        }
+    }
+
+    /// 更新処理.1回で1ティック進む.
+    /// read_inputがfalseなら新しいキー入力は読まず、前の更新で押されていたキーを押し続けていることにする.
+    pub fn update(&mut self, read_input: bool) -> bool{
+        {
+            let mut key_input = self.key_input_manager.lock().unwrap();
+            if read_input {
+                let _ = key_input.poll_input();
+            }
+            else {
+                key_input.keep_input();
+            }
+        }
         match self.state {
             GameState::Title => {
                 // タイトル画面の更新処理
@@ -99,9 +420,33 @@ impl GameManager {
                         key_input.is_down(&KeyType::MenuSelectLeft), key_input.is_down(&KeyType::MenuSelectRight), 
                         key_input.is_down(&KeyType::MenuDecide))
                 };
-                if press_select_up || press_select_down {
+                if press_select_up {
                     self.title_choice_command = match self.title_choice_command {
                         TitleChoice::Play => TitleChoice::Exit,
+                        TitleChoice::FieldSize => TitleChoice::Play,
+                        TitleChoice::RotationSystem => TitleChoice::FieldSize,
+                        TitleChoice::PieceSet => TitleChoice::RotationSystem,
+                        TitleChoice::Randomizer => TitleChoice::PieceSet,
+                        TitleChoice::Rules => TitleChoice::Randomizer,
+                        TitleChoice::Big => TitleChoice::Rules,
+                        TitleChoice::Visibility => TitleChoice::Big,
+                        TitleChoice::Gravity => TitleChoice::Visibility,
+                        TitleChoice::Options => TitleChoice::Gravity,
+                        TitleChoice::Exit => TitleChoice::Options,
+                    }
+                }
+                if press_select_down {
+                    self.title_choice_command = match self.title_choice_command {
+                        TitleChoice::Play => TitleChoice::FieldSize,
+                        TitleChoice::FieldSize => TitleChoice::RotationSystem,
+                        TitleChoice::RotationSystem => TitleChoice::PieceSet,
+                        TitleChoice::PieceSet => TitleChoice::Randomizer,
+                        TitleChoice::Randomizer => TitleChoice::Rules,
+                        TitleChoice::Rules => TitleChoice::Big,
+                        TitleChoice::Big => TitleChoice::Visibility,
+                        TitleChoice::Visibility => TitleChoice::Gravity,
+                        TitleChoice::Gravity => TitleChoice::Options,
+                        TitleChoice::Options => TitleChoice::Exit,
                         TitleChoice::Exit => TitleChoice::Play,
                     }
                 }
@@ -113,6 +458,57 @@ impl GameManager {
                             PlayStyle::VSPlayer => PlayStyle::Solo,
                         }
                     }
+                    if self.title_choice_command == TitleChoice::FieldSize {
+                        self.field_size = match self.field_size {
+                            FieldSize::Standard => FieldSize::Wide,
+                            FieldSize::Wide => FieldSize::Narrow,
+                            FieldSize::Narrow => FieldSize::Tall,
+                            FieldSize::Tall => FieldSize::Standard,
+                        }
+                    }
+                    if self.title_choice_command == TitleChoice::RotationSystem {
+                        self.rotation_system_type = match self.rotation_system_type {
+                            RotationSystemType::Srs => RotationSystemType::Classic,
+                            RotationSystemType::Classic => RotationSystemType::Ars,
+                            RotationSystemType::Ars => RotationSystemType::Srs,
+                        }
+                    }
+                    if self.title_choice_command == TitleChoice::PieceSet {
+                        self.piece_set_index = (self.piece_set_index + 1) % self.piece_sets.len();
+                    }
+                    if self.title_choice_command == TitleChoice::Randomizer {
+                        self.randomizer_type = match self.randomizer_type {
+                            RandomizerType::Bag => RandomizerType::DoubleBag,
+                            RandomizerType::DoubleBag => RandomizerType::Random,
+                            RandomizerType::Random => RandomizerType::History,
+                            RandomizerType::History => RandomizerType::Bag,
+                        }
+                    }
+                    if self.title_choice_command == TitleChoice::Rules {
+                        self.rules_type = match self.rules_type {
+                            GameRulesType::Standard => GameRulesType::ModernFast,
+                            GameRulesType::ModernFast => GameRulesType::ClassicSlow,
+                            GameRulesType::ClassicSlow => GameRulesType::Practice,
+                            GameRulesType::Practice => GameRulesType::Standard,
+                        }
+                    }
+                    if self.title_choice_command == TitleChoice::Big {
+                        self.big_mode = !self.big_mode;
+                    }
+                    if self.title_choice_command == TitleChoice::Visibility {
+                        self.visibility_type = match self.visibility_type {
+                            VisibilityType::Normal => VisibilityType::Fading,
+                            VisibilityType::Fading => VisibilityType::Invisible,
+                            VisibilityType::Invisible => VisibilityType::Normal,
+                        }
+                    }
+                    if self.title_choice_command == TitleChoice::Gravity {
+                        self.gravity_type = match self.gravity_type {
+                            GravityType::Naive => GravityType::Sticky,
+                            GravityType::Sticky => GravityType::Cascade,
+                            GravityType::Cascade => GravityType::Naive,
+                        }
+                    }
                 }
                 if press_select_left {
                     if self.title_choice_command == TitleChoice::Play {
@@ -122,11 +518,66 @@ impl GameManager {
                             PlayStyle::VSPlayer => PlayStyle::WithNPC(1),
                         }
                     }
+                    if self.title_choice_command == TitleChoice::FieldSize {
+                        self.field_size = match self.field_size {
+                            FieldSize::Standard => FieldSize::Tall,
+                            FieldSize::Wide => FieldSize::Standard,
+                            FieldSize::Narrow => FieldSize::Wide,
+                            FieldSize::Tall => FieldSize::Narrow,
+                        }
+                    }
+                    if self.title_choice_command == TitleChoice::RotationSystem {
+                        self.rotation_system_type = match self.rotation_system_type {
+                            RotationSystemType::Srs => RotationSystemType::Ars,
+                            RotationSystemType::Classic => RotationSystemType::Srs,
+                            RotationSystemType::Ars => RotationSystemType::Classic,
+                        }
+                    }
+                    if self.title_choice_command == TitleChoice::PieceSet {
+                        self.piece_set_index = (self.piece_set_index + self.piece_sets.len() - 1) % self.piece_sets.len();
+                    }
+                    if self.title_choice_command == TitleChoice::Randomizer {
+                        self.randomizer_type = match self.randomizer_type {
+                            RandomizerType::Bag => RandomizerType::History,
+                            RandomizerType::DoubleBag => RandomizerType::Bag,
+                            RandomizerType::Random => RandomizerType::DoubleBag,
+                            RandomizerType::History => RandomizerType::Random,
+                        }
+                    }
+                    if self.title_choice_command == TitleChoice::Rules {
+                        self.rules_type = match self.rules_type {
+                            GameRulesType::Standard => GameRulesType::Practice,
+                            GameRulesType::ModernFast => GameRulesType::Standard,
+                            GameRulesType::ClassicSlow => GameRulesType::ModernFast,
+                            GameRulesType::Practice => GameRulesType::ClassicSlow,
+                        }
+                    }
+                    if self.title_choice_command == TitleChoice::Big {
+                        self.big_mode = !self.big_mode;
+                    }
+                    if self.title_choice_command == TitleChoice::Visibility {
+                        self.visibility_type = match self.visibility_type {
+                            VisibilityType::Normal => VisibilityType::Invisible,
+                            VisibilityType::Fading => VisibilityType::Normal,
+                            VisibilityType::Invisible => VisibilityType::Fading,
+                        }
+                    }
+                    if self.title_choice_command == TitleChoice::Gravity {
+                        self.gravity_type = match self.gravity_type {
+                            GravityType::Naive => GravityType::Cascade,
+                            GravityType::Sticky => GravityType::Naive,
+                            GravityType::Cascade => GravityType::Sticky,
+                        }
+                    }
                 }
                 if press_decide {
                     match self.title_choice_command {
                         TitleChoice::Play => {
                             self.state = GameState::Playing;
+                            // 全員同じシードで始めるので、ブロックの順番も同じになる.
+                            // 画面から書き写せるよう、ランダムに決める場合は短めの値にする.
+                            self.seed = self.fixed_seed.unwrap_or_else(|| rand::rng().random::<u32>() as u64);
+                            self.target_rng = StdRng::seed_from_u64(self.seed);
                             match self.play_style {
                                 PlayStyle::Solo => self.create_player(PlayerType::Player1),
                                 PlayStyle::WithNPC(npc_count) => {
@@ -140,33 +591,115 @@ impl GameManager {
                                     self.create_player(PlayerType::Player2);
                                 },
                             }
+                            for gameplay_manager in &mut self.gameplay_managers {
+                                gameplay_manager.set_visibility(self.visibility_type.create());
+                                gameplay_manager.set_gravity_type(self.gravity_type);
+                            }
+                            // 対戦では攻撃表を使った攻撃力にする.
+                            if !matches!(self.play_style, PlayStyle::Solo) {
+                                for gameplay_manager in &mut self.gameplay_managers {
+                                    gameplay_manager.set_attack_power_calculator(Box::new(CompetitiveAttackPowerCalculator::new(AttackTable::standard())));
+                                }
+                            }
                             
                             self.high_score_updated = false;
+                            self.replay_message = None;
                         },
+                        TitleChoice::Options => {
+                            self.options_choice = OptionsChoice::Player;
+                            self.options_message = None;
+                            self.state = GameState::Options;
+                        },
+                        TitleChoice::FieldSize | TitleChoice::RotationSystem | TitleChoice::PieceSet | TitleChoice::Randomizer | TitleChoice::Rules | TitleChoice::Big | TitleChoice::Visibility | TitleChoice::Gravity => {},
                         TitleChoice::Exit => return false,
                     };
                 }
             }
+            GameState::Options => {
+                // 設定画面の更新処理
+                let (press_select_up, press_select_down, 
+                    press_select_left, press_select_right, 
+                    press_decide) = {
+                    let key_input = self.key_input_manager.lock().unwrap();
+                    (key_input.is_down(&KeyType::MenuSelectUp), key_input.is_down(&KeyType::MenuSelectDown), 
+                        key_input.is_down(&KeyType::MenuSelectLeft), key_input.is_down(&KeyType::MenuSelectRight), 
+                        key_input.is_down(&KeyType::MenuDecide))
+                };
+                if press_select_up {
+                    self.options_choice = match self.options_choice {
+                        OptionsChoice::Player => OptionsChoice::Back,
+                        OptionsChoice::Das => OptionsChoice::Player,
+                        OptionsChoice::Arr => OptionsChoice::Das,
+                        OptionsChoice::SoftDropFactor => OptionsChoice::Arr,
+                        OptionsChoice::DasCut => OptionsChoice::SoftDropFactor,
+                        OptionsChoice::KeepDasCharge => OptionsChoice::DasCut,
+                        OptionsChoice::Back => OptionsChoice::KeepDasCharge,
+                    }
+                }
+                if press_select_down {
+                    self.options_choice = match self.options_choice {
+                        OptionsChoice::Player => OptionsChoice::Das,
+                        OptionsChoice::Das => OptionsChoice::Arr,
+                        OptionsChoice::Arr => OptionsChoice::SoftDropFactor,
+                        OptionsChoice::SoftDropFactor => OptionsChoice::DasCut,
+                        OptionsChoice::DasCut => OptionsChoice::KeepDasCharge,
+                        OptionsChoice::KeepDasCharge => OptionsChoice::Back,
+                        OptionsChoice::Back => OptionsChoice::Player,
+                    }
+                }
+                if press_select_right {
+                    self.adjust_option(true);
+                }
+                if press_select_left {
+                    self.adjust_option(false);
+                }
+                if press_decide && self.options_choice == OptionsChoice::Back {
+                    // 全員分保存してから戻る.保存できなければ理由を表示して留まる.
+                    let result = self.profiles.iter().enumerate()
+                        .try_for_each(|(index, profile)| player_profile::save_profile(index, profile));
+                    match result {
+                        Ok(()) => self.state = GameState::Title,
+                        Err(message) => self.options_message = Some(message),
+                    }
+                }
+            }
             GameState::Playing => {
-                for gameplay_manager in &mut self.gameplay_managers {
-                    gameplay_manager.update();
-                    if gameplay_manager.pause_requested() {
+                for i in 0..self.gameplay_managers.len() {
+                    self.gameplay_managers[i].update();
+                    if self.gameplay_managers[i].pause_requested() {
                         self.state = GameState::Paused;
                     }
+                    // 最近攻撃した相手のうち最後の相手がゲームオーバーにしたことにする.
+                    // その相手も既にゲームオーバーならバッジは渡さない.
+                    let game_over = self.gameplay_managers[i].get_events().iter().any(|event| matches!(event, GameplayEvent::GameOver(_)));
+                    if game_over {
+                        let attacker = self.gameplay_managers[i].get_last_attacker()
+                            .filter(|attacker| !self.gameplay_managers[*attacker].is_game_over());
+                        if let Some(attacker) = attacker {
+                            self.gameplay_managers[attacker].add_badge();
+                        }
+                    }
                 }
                 // 攻撃受け入れ
                 for i in 0..self.gameplay_managers.len() {
                     let attack_power = self.gameplay_managers[i].pop_attack_power();
                     if attack_power > 0 {
-                        for j in 0..self.gameplay_managers.len() {
-                            if i != j {
-                                self.gameplay_managers[j].apply_attack(attack_power);
-                            }
+                        for target in self.gameplay_managers[i].get_targets().to_vec() {
+                            self.gameplay_managers[target].apply_attack(attack_power, i);
+                        }
+                        // ランダムの場合は攻撃するたびに狙い直す.
+                        if self.gameplay_managers[i].get_targeting_type() == TargetingType::Random {
+                            self.gameplay_managers[i].set_targets(vec![]);
                         }
                     }
                 }
+                self.update_targets();
                 if self.gameplay_managers.iter().all(|gm| gm.is_game_over()) {
                     self.state = GameState::GameOver;
+                    // ひとりで遊んだゲームはリプレイとして残す.
+                    if matches!(self.play_style, PlayStyle::Solo) {
+                        self.save_replay();
+                    }
                 }
             }
             GameState::Paused => {
@@ -193,8 +726,85 @@ impl GameManager {
                     self.state = GameState::Title;
                 }
             }
+            GameState::Replay => {
+                // リプレイ再生中の更新処理.
+                let (press_select_left, press_select_right, press_decide, press_quit) = {
+                    let key_input = self.key_input_manager.lock().unwrap();
+                    (key_input.is_down(&KeyType::MenuSelectLeft), key_input.is_down(&KeyType::MenuSelectRight),
+                        key_input.is_down(&KeyType::MenuDecide), key_input.is_down(&KeyType::P1Pause))
+                };
+                if press_select_right {
+                    self.replay_speed = match self.replay_speed {
+                        ReplaySpeed::Normal => ReplaySpeed::Fast,
+                        ReplaySpeed::Fast => ReplaySpeed::Step,
+                        ReplaySpeed::Step => ReplaySpeed::Normal,
+                    }
+                }
+                if press_select_left {
+                    self.replay_speed = match self.replay_speed {
+                        ReplaySpeed::Normal => ReplaySpeed::Step,
+                        ReplaySpeed::Fast => ReplaySpeed::Normal,
+                        ReplaySpeed::Step => ReplaySpeed::Fast,
+                    }
+                }
+                // 最後まで再生し終えたか、途中でやめたらタイトルに戻る.
+                let finished = self.gameplay_managers.iter().all(|gm| gm.is_game_over());
+                if press_quit || (finished && press_decide) {
+                    self.gameplay_managers.clear();
+                    self.state = GameState::Title;
+                }
+                else {
+                    let update_count = match self.replay_speed {
+                        ReplaySpeed::Normal => 1,
+                        ReplaySpeed::Fast => REPLAY_FAST_UPDATES,
+                        ReplaySpeed::Step => if press_decide {1} else {0},
+                    };
+                    for _ in 0..update_count {
+                        for gameplay_manager in &mut self.gameplay_managers {
+                            gameplay_manager.update();
+                        }
+                    }
+                }
+            }
         }
-        self.renderer_sender.game_sender(&self);
+        self.renderer_sender.game_sender(self);
         true
     }
 }
+
+/// 設定画面で時間の値を1段階変える.
+fn step_time(value: u32, forward: bool) -> u32 {
+    if forward {
+        (value + OPTIONS_TIME_STEP_MS).min(OPTIONS_TIME_MAX_MS)
+    }
+    else {
+        value.saturating_sub(OPTIONS_TIME_STEP_MS)
+    }
+}
+
+/// 設定画面でソフトドロップ倍率を1段階変える.上限の次は一番下まで落ちる設定にする.
+fn step_soft_drop_factor(value: u32, forward: bool) -> u32 {
+    match (value, forward) {
+        (SOFT_DROP_INFINITE, true) => SOFT_DROP_INFINITE,
+        (SOFT_DROP_INFINITE, false) => OPTIONS_SOFT_DROP_MAX,
+        (OPTIONS_SOFT_DROP_MAX.., true) => SOFT_DROP_INFINITE,
+        (_, true) => value + OPTIONS_SOFT_DROP_STEP,
+        (_, false) => value.saturating_sub(OPTIONS_SOFT_DROP_STEP).max(1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_options() {
+        assert_eq!(step_time(0, false), 0);
+        assert_eq!(step_time(OPTIONS_TIME_MAX_MS, true), OPTIONS_TIME_MAX_MS);
+        assert_eq!(step_time(300, true), 310);
+        assert_eq!(step_soft_drop_factor(OPTIONS_SOFT_DROP_MAX, true), SOFT_DROP_INFINITE);
+        assert_eq!(step_soft_drop_factor(SOFT_DROP_INFINITE, false), OPTIONS_SOFT_DROP_MAX);
+        assert_eq!(step_soft_drop_factor(5, false), 1);
+        assert_eq!(step_soft_drop_factor(20, true), 25);
+    }
+}
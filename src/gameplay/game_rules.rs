@@ -0,0 +1,124 @@
+//! ゲームの進行速度に関わるルール.
+//! 時間の単位は全てms.
+
+use crate::gameplay::{lock_down::LockDownType, spin_checker::SpinCheckerType};
+
+/// 1ゲーム分のルール.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct GameRules {
+    /// 接地してから固定されるまでの時間.
+    pub lock_down_time_ms: u32,
+    /// 接地中に操作できる回数.超えるとすぐに固定される.
+    /// 操作回数に上限があるロックダウンの場合のみ使う.
+    pub lock_down_count_max: u32,
+    /// ロックダウンの種類.
+    pub lock_down_type: LockDownType,
+    /// 消えるラインを見せておく時間.
+    pub erace_line_wait_ms: u32,
+    /// 消えたラインを詰めてから次に進むまでの時間.
+    pub drop_line_wait_ms: u32,
+    /// ゲーム開始から最初のブロックが出るまでの時間.
+    pub before_controlling_wait_ms: u32,
+    /// ラインを消さずに固定してから次のブロックが出るまでの時間(ARE).
+    pub spawn_delay_ms: u32,
+    /// ラインを消した後、次のブロックが出るまでの時間.
+    pub line_clear_delay_ms: u32,
+    /// スピンの判定.全てのブロックのスピンを認めるかどうか.
+    pub spin_checker_type: SpinCheckerType,
+}
+
+impl GameRules {
+    /// これまでの標準的なルール.
+    pub fn standard() -> Self {
+        GameRules {
+            lock_down_time_ms: 500,
+            lock_down_count_max: 15,
+            lock_down_type: LockDownType::MoveReset,
+            erace_line_wait_ms: 500,
+            drop_line_wait_ms: 500,
+            before_controlling_wait_ms: 0,
+            spawn_delay_ms: 0,
+            line_clear_delay_ms: 0,
+            spin_checker_type: SpinCheckerType::TSpinOnly,
+        }
+    }
+
+    /// 待ち時間の少ない、テンポの速いルール.対戦向けに、固定までの時間は段を下りたときだけ延びる.
+    /// 全てのブロックのスピンを認める.
+    pub fn modern_fast() -> Self {
+        GameRules {
+            lock_down_time_ms: 500,
+            lock_down_count_max: 15,
+            lock_down_type: LockDownType::StepReset,
+            erace_line_wait_ms: 100,
+            drop_line_wait_ms: 100,
+            before_controlling_wait_ms: 1000,
+            spawn_delay_ms: 0,
+            line_clear_delay_ms: 0,
+            spin_checker_type: SpinCheckerType::AllSpin,
+        }
+    }
+
+    /// 固定までが長く、ブロックが出るまでも待たされる、ゆっくりしたルール.
+    pub fn classic_slow() -> Self {
+        GameRules {
+            lock_down_time_ms: 1000,
+            lock_down_count_max: 15,
+            lock_down_type: LockDownType::StepReset,
+            erace_line_wait_ms: 800,
+            drop_line_wait_ms: 400,
+            before_controlling_wait_ms: 3000,
+            spawn_delay_ms: 400,
+            line_clear_delay_ms: 600,
+            spin_checker_type: SpinCheckerType::TSpinOnly,
+        }
+    }
+
+    /// 練習用のルール.操作している限り固定されない.
+    pub fn practice() -> Self {
+        GameRules {
+            lock_down_type: LockDownType::Infinite,
+            ..GameRules::standard()
+        }
+    }
+}
+
+/// タイトルで選べるルールの種類.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GameRulesType {
+    Standard,
+    ModernFast,
+    ClassicSlow,
+    Practice,
+}
+
+impl GameRulesType {
+    /// 対応するルールを返す.
+    pub fn to_rules(self) -> GameRules {
+        match self {
+            GameRulesType::Standard => GameRules::standard(),
+            GameRulesType::ModernFast => GameRules::modern_fast(),
+            GameRulesType::ClassicSlow => GameRules::classic_slow(),
+            GameRulesType::Practice => GameRules::practice(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets() {
+        let modern = GameRulesType::ModernFast.to_rules();
+        let classic = GameRulesType::ClassicSlow.to_rules();
+        assert!(modern.lock_down_time_ms <= classic.lock_down_time_ms);
+        assert!(modern.spawn_delay_ms <= classic.spawn_delay_ms);
+        assert!(modern.erace_line_wait_ms + modern.drop_line_wait_ms + modern.line_clear_delay_ms
+            < classic.erace_line_wait_ms + classic.drop_line_wait_ms + classic.line_clear_delay_ms);
+        assert_eq!(GameRulesType::Standard.to_rules(), GameRules::standard());
+        assert_eq!(GameRulesType::Practice.to_rules().lock_down_type, LockDownType::Infinite);
+        assert_eq!(modern.spin_checker_type, SpinCheckerType::AllSpin);
+        assert_eq!(classic.spin_checker_type, SpinCheckerType::TSpinOnly);
+    }
+}
@@ -0,0 +1,46 @@
+//! インゲームで起きた出来事.
+//! GameplayManagerが更新のたびに積み、表示や集計などは状態を覗く代わりにこれを見る.
+
+use crate::gameplay::{
+    block::{block_datas::BlockType, rotation_system::Orientation},
+    clear_event::ClearEvent,
+};
+use crate::utility::grid::Grid;
+
+/// ゲームオーバーの理由.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GameOverReason {
+    /// 出てきたブロックが積まれたブロックと重なった.
+    SpawnBlocked,
+    /// ブロックを完全にフィールドの外に固定した.
+    LockedOutside,
+    /// 攻撃で押し上げられたブロックが枠を越えた.
+    PushedOver,
+}
+
+/// 1回の更新で起きた出来事.
+#[derive(Clone, PartialEq, Debug)]
+pub enum GameplayEvent {
+    /// ブロックが出現した.
+    Spawned(BlockType),
+    /// 操作でブロックが移動した.移動後の位置.自動落下では出さない.
+    Moved(Grid),
+    /// ブロックが回転した.kickは使った補正表の番号で、回転の後に移動していればNone.
+    Rotated { orientation: Orientation, kick: Option<usize> },
+    /// ホールドした.ホールドに入ったブロックの種類.
+    Held(BlockType),
+    /// ハードドロップした.落ちたマス数.
+    HardDropped(u32),
+    /// ブロックを固定した.
+    Locked { block_type: BlockType, position: Grid },
+    /// ラインを消した.ラインを消さなかったスピンも含む.
+    Cleared(ClearEvent),
+    /// 届いた攻撃がせり上がった.ライン数.
+    GarbageReceived(usize),
+    /// 相殺して残った攻撃を送り出した.ライン数.
+    GarbageSent(usize),
+    /// レベルが上がった.上がった後のレベル.
+    LevelUp(u32),
+    /// ゲームオーバーになった.
+    GameOver(GameOverReason),
+}
@@ -3,24 +3,30 @@
 
 use crate::gameplay::{
     block::{
-        block_datas::{self, BlockType}, control_block::ControlBlock, hold_block::HoldBlock, next_blocks::NextBlocks
+        block_datas::BlockType, control_block::ControlBlock, hold_block::HoldBlock, next_blocks::{NextBlocks, Randomizer},
+        piece_set::PieceSet, rotation_system::{Orientation, RotationSystem},
     }, 
     controller::{self, ComputerController, PlayController, PlayerKeyAssigns,PlayerController}, 
-    field::Field, key_input::KeyInput, 
-    score_calculator::{AttackPowerCalculator, ScoreCalculator, SimpleAttackPowerCalculator, SimpleScoreCalculator}, 
-    t_spin_checker::{TSpinChecker, TSpinType}
+    field::{Field, FieldConfig, GravityType}, game_clock::GameClock, game_rules::GameRules, garbage_queue::{self, GarbageQueue}, handling_settings::HandlingSettings, key_input::KeyInput, 
+    level_curve::{self, LevelCurve},
+    lock_down::LockDownPolicy,
+    replay::{ReplayAction, ReplayFrame},
+    targeting::TargetingType,
+    clear_event::{ClearClassifier, ClearEvent},
+    gameplay_event::{GameOverReason, GameplayEvent},
+    score_calculator::{AttackPowerCalculator, ScoreCalculator, SimpleAttackPowerCalculator, StandardScoreCalculator}, 
+    spin_checker::SpinChecker,
+    t_spin_checker::TSpinType,
+    visibility::{self, AlwaysVisible, VisibilityPolicy},
 };
 use crate::utility::grid::Grid;
+use rand::{SeedableRng, rngs::StdRng};
 use std::time::{Instant, Duration};
 use std::sync::{Arc, Mutex};
 
-const DEFAULT_DROP_SPEED_MS: u32 = 1000;
-const LOCK_DOWN_TIME_MS: u32 = 500;
-const LOCK_DOWN_COUNT_MAX: u32 = 15;
-const MAX_ERACE_LINES: u32 = 3;
-const ERACE_LINE_WAIT_MILLIS: u128 = 500;
-const DROP_LINE_WAIT_MILLIS: u128 = 500;
-const BEFORE_CONTROLLING_WAIT_MILLIS: u128 = 3000;
+/// この時間内に攻撃してきた相手を「攻撃してきた相手」として扱う.
+const ATTACKER_MEMORY_MILLIS: u128 = 10000;
+
 enum PlayState {
     WaitStart,
     Waiting,
@@ -31,6 +37,7 @@ enum PlayState {
     Dropping,
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum PlayerType {
     Player1,
     Player2,
@@ -44,10 +51,76 @@ pub struct GameplayStats {
     pub t_spin_erace_lines: u32,
     pub combos: u32,
     pub max_erace_count: u32,
+    pub max_combos: u32,
+    pub max_chains: u32,
+    pub t_spins: u32,
+    pub t_spin_minis: u32,
+    pub back_to_backs: u32,
+    pub perfect_clears: u32,
+}
+
+impl GameplayStats {
+    /// 新規インスタンス作成.
+    pub fn new(level: u32) -> Self {
+        GameplayStats {
+            level,
+            erace_lines: 0,
+            t_spin_erace_lines: 0,
+            combos: 0,
+            max_erace_count: 0,
+            max_combos: 0,
+            max_chains: 0,
+            t_spins: 0,
+            t_spin_minis: 0,
+            back_to_backs: 0,
+            perfect_clears: 0,
+        }
+    }
+
+    /// 消し方を集計する.レベルは別で更新する.
+    /// max_erace_linesはブロックセットで決まる一度に消せる最大のライン数.
+    pub fn record(&mut self, event: &ClearEvent, max_erace_lines: u32) {
+        self.erace_lines += event.lines;
+        self.combos = event.combo;
+        self.max_combos = self.max_combos.max(event.combo);
+        self.max_chains = self.max_chains.max(event.chain);
+        if event.lines == max_erace_lines {
+            self.max_erace_count += 1;
+        }
+        // Tスピンの集計はTブロックのスピンだけ.
+        if event.spin_block == BlockType::T {
+            match event.t_spin {
+                TSpinType::Full => self.t_spins += 1,
+                TSpinType::Mini => self.t_spin_minis += 1,
+                TSpinType::None | TSpinType::Other => {},
+            }
+            self.t_spin_erace_lines += event.lines;
+        }
+        if event.back_to_back {
+            self.back_to_backs += 1;
+        }
+        if event.perfect_clear {
+            self.perfect_clears += 1;
+        }
+    }
+}
+
+/// インゲームを作るための設定.
+/// 同じシードを渡せば、ブロックの順番とせり上がりの穴の位置が同じになる.
+pub struct GameplaySettings {
+    pub level: u32,
+    pub field_config: FieldConfig,
+    pub rules: GameRules,
+    pub rotation_system: Arc<dyn RotationSystem + Send + Sync>,
+    pub piece_set: Arc<PieceSet>,
+    pub randomizer: Box<dyn Randomizer + Send>,
+    pub seed: u64,
 }
+
 /// インゲームを管理・運営していく構造体.
 pub struct GameplayManager {
     field: Field,
+    piece_set: Arc<PieceSet>,
     next_blocks: NextBlocks,
     hold_block: HoldBlock,
     control_block: ControlBlock,
@@ -56,171 +129,238 @@ pub struct GameplayManager {
     score_calculator: Box<dyn ScoreCalculator + Send>,
     attack_power_calculator: Box<dyn AttackPowerCalculator + Send>,
     attack_power: usize,
-    applied_attack: usize,
+    garbage_queue: GarbageQueue,
+    attacked_times: Vec<(usize, Instant)>,
+    last_attacker: Option<usize>,
+    targeting_type: TargetingType,
+    targets: Vec<usize>,
+    badges: u32,
     score: u64,
     stats: GameplayStats,
     state: PlayState,
     wait_timer: Instant,
-    t_spin_checker: TSpinChecker,
+    spawn_wait_ms: u32,
+    rules: GameRules,
+    spin_checker: Box<dyn SpinChecker + Send>,
     t_spin_mode: TSpinType,
-    combo_mode: bool,
+    spin_block: BlockType,
+    clear_classifier: ClearClassifier,
+    last_clear_event: Option<(ClearEvent, Instant)>,
+    level_curve: LevelCurve,
     drop_speed: u32,
-    move_counter: u32,
-    lock_down_timer: Instant,
-    lock_down_lowest_height: i32,
+    lock_down: Box<dyn LockDownPolicy + Send>,
+    visibility: Box<dyn VisibilityPolicy + Send>,
+    reveal_until: Option<Instant>,
+    gravity_type: GravityType,
+    chain: u32,
     last_drop_time: Instant,
+    events: Vec<GameplayEvent>,
+    frame: u64,
+    replay_frames: Vec<ReplayFrame>,
+    clock: Arc<dyn GameClock + Send + Sync>,
+    rng: StdRng,
 }
 
 impl GameplayManager {
     /// 有人プレイヤーでの新規インスタンス作成.
-    pub fn with_player_controller(level: u32, player_type: PlayerType, key_input: Arc<Mutex<dyn KeyInput + Send>>) -> Self {
+    pub fn with_player_controller(settings: GameplaySettings, player_type: PlayerType, handling: HandlingSettings, key_input: Arc<Mutex<dyn KeyInput + Send>>, clock: Arc<dyn GameClock + Send + Sync>) -> Self {
         let key_assigns = match player_type {
             PlayerType::Player1 => PlayerKeyAssigns::player1_keys(),
             PlayerType::Player2 => PlayerKeyAssigns::player2_keys(),
             PlayerType::NPC => panic!("NPC cannot use player controller."),
         };
-        GameplayManager::new(level, Box::new(PlayerController::new(key_assigns, key_input)))
+        GameplayManager::new(settings, Box::new(PlayerController::new(key_assigns, handling, key_input, clock.clone())), clock)
     }
 
     // NPCでの新規インスタンス作成.
-    pub fn with_npc_controller(level: u32) -> Self {
-        GameplayManager::new(level, Box::new(ComputerController::new()))
+    pub fn with_npc_controller(settings: GameplaySettings, clock: Arc<dyn GameClock + Send + Sync>) -> Self {
+        GameplayManager::new(settings, Box::new(ComputerController::new()), clock)
     }
 
     /// 新規インスタンス作成.操作するためのインスタンスが必要.
-    pub fn new(level: u32, controller: Box<dyn PlayController + Send>) -> Self {
+    /// 時間はclockで測り、更新のたびに1ティック進める.同じ操作をすれば同じ結果になる.
+    pub fn new(settings: GameplaySettings, controller: Box<dyn PlayController + Send>, clock: Arc<dyn GameClock + Send + Sync>) -> Self {
+        let GameplaySettings { level, field_config, rules, rotation_system, piece_set, randomizer, seed } = settings;
+        let now = clock.now();
+        let mut rng = StdRng::seed_from_u64(seed);
+        // ブロック用の乱数は親から分けて持たせる.先読みの量でせり上がりの結果が変わらないようにするため.
+        let next_blocks_rng = StdRng::from_rng(&mut rng);
+        let max_erace_lines = piece_set.max_erace_lines();
         GameplayManager {
-            field: Field::new(),
-            next_blocks: NextBlocks::new(),
+            field: Field::new(field_config),
+            next_blocks: NextBlocks::new(randomizer, next_blocks_rng),
             hold_block: HoldBlock::new(),
-            control_block: ControlBlock::new(),
+            control_block: ControlBlock::new(rotation_system, piece_set.clone(), field_config.block_scale),
+            piece_set,
             is_game_over: false,
             controller: controller,
-            score_calculator: Box::new(SimpleScoreCalculator::new()),
+            score_calculator: Box::new(StandardScoreCalculator::new()),
             attack_power_calculator: Box::new(SimpleAttackPowerCalculator::new()),
             attack_power: 0,
-            applied_attack: 0,
+            garbage_queue: GarbageQueue::new(Duration::from_millis(garbage_queue::GARBAGE_DELAY_MILLIS)),
+            attacked_times: vec![],
+            last_attacker: None,
+            targeting_type: TargetingType::All,
+            targets: vec![],
+            badges: 0,
             score: 0,
-            stats: GameplayStats {
-                erace_lines: 0,
-                t_spin_erace_lines: 0,
-                combos: 0,
-                max_erace_count: 0,
-                level: level,
-            },
+            stats: GameplayStats::new(level),
             state: PlayState::WaitStart,
-            wait_timer: Instant::now(),
-            t_spin_checker: TSpinChecker::new(),
+            wait_timer: now,
+            spawn_wait_ms: rules.before_controlling_wait_ms,
+            rules,
+            spin_checker: rules.spin_checker_type.create(),
             t_spin_mode: TSpinType::None,
-            combo_mode: false,
-            drop_speed: DEFAULT_DROP_SPEED_MS,
-            move_counter: 0,
-            lock_down_timer: Instant::now(),
-            lock_down_lowest_height: 0,
-            last_drop_time: Instant::now(),
+            spin_block: BlockType::None,
+            clear_classifier: ClearClassifier::new(max_erace_lines),
+            last_clear_event: None,
+            level_curve: LevelCurve::new(level, level_curve::DEFAULT_LINES_PER_LEVEL),
+            drop_speed: LevelCurve::drop_speed_ms(level),
+            lock_down: rules.lock_down_type.create(rules.lock_down_time_ms, rules.lock_down_count_max),
+            visibility: Box::new(AlwaysVisible::new()),
+            reveal_until: None,
+            gravity_type: GravityType::Naive,
+            chain: 0,
+            last_drop_time: now,
+            events: vec![],
+            frame: 0,
+            replay_frames: vec![],
+            clock,
+            rng,
         }
     }
     
     /// インゲームの更新処理.
+    /// 前回の更新で起きた出来事は消して、今回の分を積み直す.
+    /// 操作された内容は、何フレーム目かと一緒にリプレイ用に記録する.
     pub fn update(&mut self) {
+        self.events.clear();
+        self.clock.advance();
         if self.is_game_over {
             return;
         }
-        let now = Instant::now();
+        self.controller.begin_frame(self.frame);
+        let mut actions = vec![];
+        if self.controller.is_target_change_requested() {
+            self.targeting_type = self.targeting_type.next();
+            self.targets.clear();
+            actions.push(ReplayAction::ChangeTarget);
+        }
+        self.update_state();
+        actions.append(&mut self.controller.take_applied_actions());
+        if !actions.is_empty() {
+            self.replay_frames.push(ReplayFrame { frame: self.frame, actions });
+        }
+        self.frame += 1;
+    }
+
+    /// 状態ごとの更新処理.
+    fn update_state(&mut self) {
+        let now = self.clock.now();
+        // 操作できない間に押されたキーは覚えておき、操作できるようになったら使う.
+        if !matches!(self.state, PlayState::Controlling) {
+            self.controller.buffer_input();
+        }
         match self.state {
             PlayState::WaitStart => {
                 // 待機処理前にしたいことをする.
-                // 攻撃を受けていたらここで受け入れる.
-                if self.applied_attack > 0 {
-                    self.field.apply_attack(self.applied_attack);
-                    self.applied_attack = 0;
+                // 届いた攻撃があればここで受け入れる.
+                let arrived_lines = self.garbage_queue.pop_arrived(now);
+                if arrived_lines > 0 {
+                    self.field.apply_attack(arrived_lines, &mut self.rng, now);
+                    self.events.push(GameplayEvent::GarbageReceived(arrived_lines));
                 }
                 self.state = PlayState::Waiting;
             }
             PlayState::Waiting => {
                 // 次のブロックが来るまでの待機処理
-                if self.wait_timer.elapsed().as_millis() <= BEFORE_CONTROLLING_WAIT_MILLIS {
+                // ゲーム開始時、固定後、ライン消去後でそれぞれ待ち時間が違う.
+                if now.duration_since(self.wait_timer).as_millis() >= self.spawn_wait_ms as u128 {
                     self.state = PlayState::StartControlling;
                 }
             }
             PlayState::StartControlling => {
                 // ゲームオーバーのチェック
-                if self.field.check_game_over(&self.next_blocks.show_next_block(0)) {
-                    self.is_game_over = true;
+                let next_block = self.next_blocks.show_next_block(0);
+                if self.field.check_game_over(&self.piece_set.shape(next_block), &self.piece_set.spawn_offset(next_block)) {
+                    let reason = self.field.get_forced_game_over().unwrap_or(GameOverReason::SpawnBlocked);
+                    self.set_game_over(reason);
                     return;
                 }
                 // 状態のリセット
                 self.hold_block.allow_hold();
                 self.t_spin_mode = TSpinType::None;
-                self.move_counter = 0;
-                self.lock_down_timer = Instant::now();
-                self.lock_down_lowest_height = 0;
-                self.last_drop_time = Instant::now();
+                self.last_drop_time = now;
                 // ブロックの配置.
-                self.control_block.apply_block(self.next_blocks.next(), block_datas::BLOCK_START_POSITION);
+                self.control_block.apply_block(self.next_blocks.next(), self.field.get_config().spawn_position());
+                // 出現時の回転とホールド.ホールドしたブロックが重なっていればゲームオーバー.
+                let holding_block = self.hold_block.get_holding_block();
+                self.controller.spawn_control(&mut self.control_block, &mut self.hold_block, &self.field, &mut self.next_blocks);
+                if self.hold_block.get_holding_block() != holding_block {
+                    self.events.push(GameplayEvent::Held(self.hold_block.get_holding_block()));
+                }
+                if self.control_block.orientation != Orientation::Spawn {
+                    self.events.push(GameplayEvent::Rotated { orientation: self.control_block.orientation, kick: self.control_block.get_last_rotation_kick() });
+                }
+                self.events.push(GameplayEvent::Spawned(self.control_block.block_type));
+                if self.field.check_collision(&self.control_block.block, &self.control_block.position) {
+                    self.set_game_over(GameOverReason::SpawnBlocked);
+                    return;
+                }
+                self.lock_down.reset(self.control_block.position.y, now);
                 self.state = PlayState::Controlling;
                 // 操作プランの策定.
                 let next_hold_block = if self.hold_block.get_holding_block() == BlockType::None {self.next_blocks.show_next_block(0)} else {self.hold_block.get_holding_block()};
-                self.controller.plan(&self.control_block.block_type, &next_hold_block, &self.field);
+                self.controller.plan(&self.control_block.block_type, &next_hold_block, &self.field, &self.piece_set);
             }
             PlayState::Controlling => {
                 // 操作可能状態での処理.
                 // 自動落下処理
-                let now = Instant::now();
                 let down_count = now.duration_since(self.last_drop_time).as_millis() as u32 / self.drop_speed;
                 for _ in 0..down_count {
-                    self.control_block.down(&self.field);
+                    if !self.control_block.down(&self.field) {
+                        break;
+                    }
                 }
                 if down_count > 0 {
                     let remain = Duration::from_millis(now.duration_since(self.last_drop_time).as_millis() as u64 % self.drop_speed as u64);
                     self.last_drop_time = now - remain;
-                    self.lock_down_timer = Instant::now();
-                    self.t_spin_mode = TSpinType::None;
                 }
                 // プレイヤー操作処理
-                self.t_spin_checker.set_block_data(&self.control_block);
+                let position = self.control_block.position.clone();
+                let orientation = self.control_block.orientation;
                 let move_count = self.controller.control(&mut self.control_block, &mut self.hold_block, &self.field, &mut self.next_blocks, self.drop_speed as u128, down_count);
+                let hard_dropped = move_count >= controller::HARD_DROP_MOVE_COUNT;
                 if move_count == controller::HOLD_USING {
                     //ホールドされたのでロックダウン周りはリセット.
-                    self.move_counter = 0;
-                    self.lock_down_timer = Instant::now();
+                    self.lock_down.reset(self.control_block.position.y, now);
+                    self.events.push(GameplayEvent::Held(self.hold_block.get_holding_block()));
                 }
-                else if move_count > 0 {
-                    self.move_counter += move_count as u32;
-                    self.lock_down_timer = Instant::now();
-                    
-                    self.t_spin_mode = if self.t_spin_checker.check_t_spinned(&self.control_block){
-                            // Tブロックが回転しているのでTスピン判定
-                            // フィールド情報の一部抜粋.
-                            let mut t_block_field = vec![vec![BlockType::None; 3]; 3];
-                            for x in 0..3{
-                                for y in 0..3{
-                                    let check_pos = Grid::new(self.control_block.position.x + x as i32, self.control_block.position.y - y as i32);
-                                    t_block_field[y][x] = if !self.field.check_position_in_field(&check_pos){
-                                            // とりあえずNone以外ならなんでもいい.
-                                            BlockType::I
-                                        }
-                                        else{
-                                            self.field.get_grid_data(&check_pos)
-                                        };
-                                }
-                            }
-                            self.t_spin_checker.calc_t_spin_type(&self.control_block, &t_block_field)
-                        }
-                        else {
-                            TSpinType::None
-                        };
+                else if hard_dropped {
+                    self.events.push(GameplayEvent::HardDropped(self.control_block.get_hard_drop_cells()));
                 }
-                // ハードドロップでなく、下限値更新していたら移動回数をリセット.
-                // lowestだけど、下向きに正のため大きい方が下に来る.
-                if self.move_counter < controller::HARD_DROP_MOVE_COUNT as u32 && self.control_block.position.y > self.lock_down_lowest_height {
-                    self.lock_down_lowest_height = self.control_block.position.y;
-                    self.move_counter = 0;
+                else if move_count > 0 {
+                    self.lock_down.on_move(move_count as u32, now);
+                    let kick = self.control_block.get_last_rotation_kick();
+                    if self.control_block.orientation != orientation {
+                        self.events.push(GameplayEvent::Rotated { orientation: self.control_block.orientation, kick });
+                    }
+                    // 回転の補正による移動は回転として扱う.
+                    if self.control_block.position != position && (self.control_block.orientation == orientation || kick.is_none()) {
+                        self.events.push(GameplayEvent::Moved(self.control_block.position.clone()));
+                    }
                 }
-                // ロックダウン判定
+                // 自動落下と操作の両方が終わった段を伝える.
+                self.lock_down.on_row(self.control_block.position.y, now);
+                // ロックダウン判定.ハードドロップはすぐに固定する.
                 if self.field.check_collision(&self.control_block.block, &Grid::new(self.control_block.position.x, self.control_block.position.y + 1)) {
-                    if self.move_counter >= LOCK_DOWN_COUNT_MAX || now.duration_since(self.lock_down_timer).as_millis() as u32 >= LOCK_DOWN_TIME_MS {
-                        self.field.lock_block(&self.control_block.block, &self.control_block.position);
+                    if hard_dropped || self.lock_down.should_lock(now) {
+                        // スピン判定は固定するときに1回だけ行う.回転の後に落としていれば最後の操作は回転ではない.
+                        self.t_spin_mode = self.spin_checker.calc_spin_type(&self.control_block, &self.field);
+                        self.field.lock_block(&self.control_block.block, &self.control_block.position, now);
+                        self.score += self.score_calculator.calc_drop(self.control_block.get_soft_drop_cells(), self.control_block.get_hard_drop_cells());
+                        self.spin_block = if self.t_spin_mode != TSpinType::None {self.control_block.block_type} else {BlockType::None};
+                        self.events.push(GameplayEvent::Locked { block_type: self.control_block.block_type, position: self.control_block.position.clone() });
                         self.control_block.delete_block();
                         self.state = PlayState::Dropped;
                     }
@@ -229,45 +369,115 @@ impl GameplayManager {
             PlayState::Dropped => {
                 // ラインクリアのチェック.
                 let eraced_lines = self.field.clear_lines();
+                let event = self.clear_classifier.classify(eraced_lines, self.t_spin_mode, self.spin_block, self.field.is_empty());
+                self.apply_clear_event(&event, now);
                 if eraced_lines > 0 {
-                    self.stats.erace_lines += eraced_lines;
-                    if eraced_lines == MAX_ERACE_LINES {
-                        self.stats.max_erace_count += 1;
-                    }
-                    if self.t_spin_mode != TSpinType::None {
-                        self.stats.t_spin_erace_lines += eraced_lines;
-                    }
-                    self.score += self.score_calculator.calc(eraced_lines, self.t_spin_mode, self.stats.combos); 
-                    self.combo_mode = true;
-                    self.stats.combos += 1;
-                    self.attack_power = self.attack_power_calculator.calc(eraced_lines, self.t_spin_mode, self.stats.combos);
                     self.state = PlayState::Eracing;
                 }
                 else{
-                    self.combo_mode = false;
-                    self.stats.combos = 0;
+                    self.spawn_wait_ms = self.rules.spawn_delay_ms;
                     self.state = PlayState::WaitStart;
                 }
                 self.wait_timer = now;
             }
             PlayState::Eracing => {
                 // ライン消去中の処理.
-                if self.wait_timer.elapsed().as_millis() >= ERACE_LINE_WAIT_MILLIS {
+                if now.duration_since(self.wait_timer).as_millis() >= self.rules.erace_line_wait_ms as u128 {
                     self.wait_timer = now;
                     self.state = PlayState::Dropping;
                 }
             }
             PlayState::Dropping => {
                 // 空白ライン埋めの処理.
-                self.field.drop_lines();
-                if self.wait_timer.elapsed().as_millis() >= DROP_LINE_WAIT_MILLIS {
+                self.field.apply_gravity(self.gravity_type);
+                if now.duration_since(self.wait_timer).as_millis() >= self.rules.drop_line_wait_ms as u128 {
                     self.wait_timer = now;
-                    self.state = PlayState::WaitStart;
+                    // 落ちたブロックでラインが揃ったら連鎖として消す.揃わなくなるまで繰り返す.
+                    let eraced_lines = self.field.clear_lines();
+                    if eraced_lines > 0 {
+                        self.chain += 1;
+                        let event = self.clear_classifier.classify_chain(eraced_lines, self.chain, self.field.is_empty());
+                        self.apply_clear_event(&event, now);
+                        self.state = PlayState::Eracing;
+                    }
+                    else {
+                        self.chain = 0;
+                        self.spawn_wait_ms = self.rules.line_clear_delay_ms;
+                        self.state = PlayState::WaitStart;
+                    }
                 }
             }
         }
     }
 
+    /// 消し方を点数、ステータス、攻撃に反映する.
+    fn apply_clear_event(&mut self, event: &ClearEvent, now: Instant) {
+        // ラインを消していなくてもTスピンなら点数が入る.
+        self.score += self.score_calculator.calc(event, self.stats.level);
+        self.stats.record(event, self.piece_set.max_erace_lines());
+        if event.lines > 0 || event.t_spin != TSpinType::None {
+            self.last_clear_event = Some((*event, now));
+            self.events.push(GameplayEvent::Cleared(*event));
+        }
+        if event.lines > 0 {
+            self.update_level();
+            // 溜まっている攻撃を先に相殺して、余った分だけ相手に送る.
+            let attack_power = self.garbage_queue.cancel(self.attack_power_calculator.calc(event));
+            if attack_power > 0 {
+                self.events.push(GameplayEvent::GarbageSent(attack_power));
+            }
+            self.attack_power += attack_power;
+            if self.visibility.reveals_on_clear() {
+                self.reveal_until = Some(now + Duration::from_millis(visibility::STACK_REVEAL_MILLIS));
+            }
+        }
+    }
+
+    /// 消したライン数に応じてレベルと落下速度を更新する.
+    fn update_level(&mut self) {
+        let level = self.level_curve.calc_level(self.stats.erace_lines);
+        if level != self.stats.level {
+            if level > self.stats.level {
+                self.events.push(GameplayEvent::LevelUp(level));
+            }
+            self.stats.level = level;
+            self.drop_speed = LevelCurve::drop_speed_ms(level);
+        }
+    }
+
+    /// ゲームオーバーにする.
+    fn set_game_over(&mut self, reason: GameOverReason) {
+        self.is_game_over = true;
+        self.events.push(GameplayEvent::GameOver(reason));
+    }
+
+    /// 攻撃力の計算方法を差し替える.
+    pub fn set_attack_power_calculator(&mut self, attack_power_calculator: Box<dyn AttackPowerCalculator + Send>) {
+        self.attack_power_calculator = attack_power_calculator;
+    }
+
+    /// ライン消去後のブロックの落ち方を差し替える.
+    pub fn set_gravity_type(&mut self, gravity_type: GravityType) {
+        self.gravity_type = gravity_type;
+    }
+
+    /// スタックの見せ方を差し替える.
+    pub fn set_visibility(&mut self, visibility: Box<dyn VisibilityPolicy + Send>) {
+        self.visibility = visibility;
+    }
+
+    /// フィールドのpositionのマスを、nowの時点で表示するかどうかを返す.
+    /// ゲームオーバー後と、ラインを消して見せている間は全て表示する.
+    pub fn is_cell_visible(&self, position: &Grid, now: Instant) -> bool {
+        if self.is_game_over || self.reveal_until.is_some_and(|until| now < until) {
+            return true;
+        }
+        match self.field.get_lock_time(position) {
+            Some(lock_time) => self.visibility.is_visible(lock_time, now),
+            None => true,
+        }
+    }
+
     /// 攻撃力を取り出す.1度取り出したら0にしてしまう.
     pub fn pop_attack_power(&mut self) -> usize {
         let power = self.attack_power;
@@ -275,9 +485,61 @@ impl GameplayManager {
         power
     }
 
-    /// 攻撃を受け入れる.
-    pub fn apply_attack(&mut self, attack_power: usize) {
-        self.applied_attack += attack_power;
+    /// attackerからの攻撃を受け入れる.
+    /// すぐにはせり上がらず、一定時間経ってから届く.
+    pub fn apply_attack(&mut self, attack_power: usize, attacker: usize) {
+        let now = self.clock.now();
+        self.garbage_queue.push(attack_power, now);
+        self.attacked_times.retain(|(index, _)| *index != attacker);
+        self.attacked_times.push((attacker, now));
+        self.last_attacker = Some(attacker);
+    }
+
+    /// 最近attackerから攻撃を受けたかどうかを返す.
+    pub fn is_attacked_by(&self, attacker: usize) -> bool {
+        self.attacked_times.iter()
+            .any(|(index, time)| *index == attacker && self.clock.now().duration_since(*time).as_millis() <= ATTACKER_MEMORY_MILLIS)
+    }
+
+    /// 最後に攻撃してきた相手を返す.ゲームオーバーにした相手にバッジを渡すために使う.
+    /// 攻撃されてから時間が経っていればNone.
+    pub fn get_last_attacker(&self) -> Option<usize> {
+        self.last_attacker.filter(|attacker| self.is_attacked_by(*attacker))
+    }
+
+    /// 相手をゲームオーバーにしたのでバッジを増やす.
+    pub fn add_badge(&mut self) {
+        self.badges += 1;
+    }
+
+    /// 持っているバッジの数を返す.
+    pub fn get_badges(&self) -> u32 {
+        self.badges
+    }
+
+    /// 攻撃先の選び方を返す.
+    pub fn get_targeting_type(&self) -> TargetingType {
+        self.targeting_type
+    }
+
+    /// 今狙っている相手のインデックスを返す.
+    pub fn get_targets(&self) -> &[usize] {
+        &self.targets
+    }
+
+    /// 狙う相手を設定する.
+    pub fn set_targets(&mut self, targets: Vec<usize>) {
+        self.targets = targets;
+    }
+
+    /// 積み上がっている高さを返す.
+    pub fn get_stack_height(&self) -> usize {
+        self.field.calc_stack_height()
+    }
+
+    /// まだ届いていない攻撃のライン数を返す.
+    pub fn get_pending_garbage(&self) -> usize {
+        self.garbage_queue.pending_lines()
     }
 
     /// ゲームオーバーになったかどうかを返す.
@@ -310,6 +572,16 @@ impl GameplayManager {
         self.field.get_all_grid_data()
     }
 
+    /// フィールドの設定を返す.
+    pub fn get_field_config(&self) -> &FieldConfig {
+        self.field.get_config()
+    }
+
+    /// 使っているブロックセットを返す.
+    pub fn get_piece_set(&self) -> &PieceSet {
+        &self.piece_set
+    }
+
     /// 影の位置を返す.
     pub fn get_ghost_pos(&self) -> Grid {
         self.field.get_ghost_position(&self.control_block.block, &self.control_block.position)
@@ -324,9 +596,168 @@ impl GameplayManager {
     pub fn get_hold_block(&self) -> BlockType {
         self.hold_block.get_holding_block()
     }
+    /// ゲーム内の現在時刻を返す.表示の時間もこれで測る.
+    pub fn get_now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    /// 直前の更新で起きた出来事を、起きた順に返す.次の更新で消える.
+    pub fn get_events(&self) -> &[GameplayEvent] {
+        &self.events
+    }
+
+    /// これまでに記録した操作を返す.
+    pub fn get_replay_frames(&self) -> &[ReplayFrame] {
+        &self.replay_frames
+    }
+
+    /// 直前の消去(ラインを消さないTスピンを含む)と、それが起きた時刻を返す.
+    pub fn get_last_clear_event(&self) -> Option<(&ClearEvent, Instant)> {
+        self.last_clear_event.as_ref().map(|(event, time)| (event, *time))
+    }
+
     /// スコア以外のステータス文字列を返す.
     /// 幅は半角19字.
     pub fn get_stats(&self) -> &GameplayStats {
         &self.stats
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::{
+        block::{next_blocks::RandomizerType, rotation_system::RotationSystemType},
+        game_clock::{self, TickClock},
+        replay::{ReplayController, ReplayFrame},
+    };
+
+    /// 4マスのブロックセットと10x20のフィールドで、controllerが操作するインゲームを作る.
+    fn make_gameplay(controller: Box<dyn PlayController + Send>, level: u32, seed: u64) -> GameplayManager {
+        let piece_set = Arc::new(PieceSet::builtin().remove(1));
+        let settings = GameplaySettings {
+            level,
+            field_config: FieldConfig::wide(),
+            rules: GameRules::standard(),
+            rotation_system: RotationSystemType::Srs.create(),
+            randomizer: RandomizerType::Bag.create(piece_set.block_types()),
+            piece_set,
+            seed,
+        };
+        GameplayManager::new(settings, controller, Arc::new(TickClock::new()))
+    }
+
+    fn make_npc(seed: u64) -> GameplayManager {
+        make_gameplay(Box::new(ComputerController::new()), 1, seed)
+    }
+
+    #[test]
+    fn test_deterministic_updates() {
+        // 時間は更新の回数だけで進むので、待たずに何分でも進められて、毎回同じ結果になる.
+        let mut gameplay1 = make_npc(42);
+        let mut gameplay2 = make_npc(42);
+        let mut locked_count = 0;
+        for _ in 0..game_clock::TICKS_PER_SECOND * 120 {
+            gameplay1.update();
+            gameplay2.update();
+            assert_eq!(gameplay1.get_events(), gameplay2.get_events());
+            locked_count += gameplay1.get_events().iter().filter(|event| matches!(event, GameplayEvent::Locked { .. })).count();
+        }
+        assert!(locked_count > 0);
+        assert_eq!(gameplay1.get_field_data(), gameplay2.get_field_data());
+        assert_eq!(gameplay1.get_stats().erace_lines, gameplay2.get_stats().erace_lines);
+    }
+
+    /// 記録した操作で動かすインゲームを作り、Tブロックを空中で操作できる状態にする.
+    /// 右に回転した先で四隅のうち3つが埋まるよう、左上、右上、左下の隅を埋めておく.
+    /// groundedなら右下の隅も埋めて、回転した後に落ちないようにする.
+    fn make_mid_air_t(frames: Vec<ReplayFrame>, grounded: bool) -> GameplayManager {
+        let mut gameplay = make_gameplay(Box::new(ReplayController::new(frames)), 1, 0);
+        let top = gameplay.field.get_config().total_height() as i32 - 10;
+        let now = gameplay.clock.now();
+        let mut corners = vec![(3, top), (5, top), (3, top + 2)];
+        if grounded {
+            corners.push((5, top + 2));
+        }
+        for (x, y) in corners {
+            gameplay.field.lock_block(&vec![vec![BlockType::Attacked]], &Grid::new(x, y), now);
+        }
+        gameplay.control_block.apply_block(BlockType::T, gameplay.field.get_config().spawn_position());
+        gameplay.control_block.position = Grid::new(3, top + 2);
+        gameplay.lock_down.reset(top + 2, now);
+        gameplay.state = PlayState::Controlling;
+        gameplay
+    }
+
+    /// ブロックを固定して消去の判定が終わるまで更新して、その間の出来事を返す.
+    fn run_until_locked(gameplay: &mut GameplayManager) -> Vec<GameplayEvent> {
+        let mut events = vec![];
+        for _ in 0..game_clock::TICKS_PER_SECOND * 5 {
+            gameplay.update();
+            events.extend_from_slice(gameplay.get_events());
+            if matches!(gameplay.state, PlayState::WaitStart | PlayState::Eracing) {
+                break;
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn test_spin_then_drop() {
+        // 回転して、そのまま固定すればスピン.
+        let mut gameplay = make_mid_air_t(vec![
+            ReplayFrame { frame: 0, actions: vec![ReplayAction::Rotate] },
+        ], true);
+        let events = run_until_locked(&mut gameplay);
+        assert!(events.iter().any(|event| matches!(event, GameplayEvent::Cleared(clear) if clear.t_spin != TSpinType::None)));
+
+        // 回転した後にハードドロップやソフトドロップで落とせば、スピンではない.
+        for drop in [ReplayAction::HardDrop, ReplayAction::SoftDrop] {
+            let mut gameplay = make_mid_air_t(vec![
+                ReplayFrame { frame: 0, actions: vec![ReplayAction::Rotate] },
+                ReplayFrame { frame: 1, actions: vec![drop; 20] },
+            ], false);
+            let events = run_until_locked(&mut gameplay);
+            assert!(events.iter().any(|event| matches!(event, GameplayEvent::Locked { .. })));
+            assert!(!events.iter().any(|event| matches!(event, GameplayEvent::Cleared(_))));
+        }
+    }
+
+    #[test]
+    fn test_last_attacker_expires() {
+        let mut gameplay = make_npc(0);
+        assert_eq!(gameplay.get_last_attacker(), None);
+        gameplay.apply_attack(1, 2);
+        assert_eq!(gameplay.get_last_attacker(), Some(2));
+        assert!(gameplay.is_attacked_by(2));
+        // 覚えておく時間を過ぎたら、攻撃してきた相手ではなくなる.
+        for _ in 0..=ATTACKER_MEMORY_MILLIS as u64 * game_clock::TICKS_PER_SECOND / 1000 {
+            gameplay.clock.advance();
+        }
+        assert!(!gameplay.is_attacked_by(2));
+        assert_eq!(gameplay.get_last_attacker(), None);
+    }
+
+    #[test]
+    fn test_gravity_on_ground_does_not_extend_lock_down() {
+        // 落下間隔がロックダウンの時間より短くても、接地中の自動落下で固定が延びることはない.
+        let level = 10;
+        assert!(LevelCurve::drop_speed_ms(level) < GameRules::standard().lock_down_time_ms);
+        let mut gameplay = make_gameplay(Box::new(ReplayController::new(vec![])), level, 0);
+        let now = gameplay.clock.now();
+        gameplay.control_block.apply_block(BlockType::T, gameplay.field.get_config().spawn_position());
+        gameplay.control_block.position = gameplay.field.get_ghost_position(&gameplay.control_block.block, &gameplay.control_block.position);
+        gameplay.lock_down.reset(gameplay.control_block.position.y, now);
+        gameplay.last_drop_time = now;
+        gameplay.state = PlayState::Controlling;
+        let lock_down_ticks = (GameRules::standard().lock_down_time_ms as u64 * game_clock::TICKS_PER_SECOND).div_ceil(1000);
+        let mut locked_tick = None;
+        for tick in 1..=game_clock::TICKS_PER_SECOND * 2 {
+            gameplay.update();
+            if gameplay.get_events().iter().any(|event| matches!(event, GameplayEvent::Locked { .. })) {
+                locked_tick = Some(tick);
+                break;
+            }
+        }
+        assert_eq!(locked_tick, Some(lock_down_ticks));
+    }
+}
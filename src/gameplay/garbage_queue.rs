@@ -0,0 +1,112 @@
+//! 受けた攻撃(せり上がり)を、届くまでの間溜めておく.
+//! 溜まっている間に自分がラインを消せば、その分を相殺出来る.
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// 攻撃を受けてからフィールドに届くまでの時間.
+pub const GARBAGE_DELAY_MILLIS: u64 = 1000;
+
+/// 1回分の攻撃.
+struct AttackPacket {
+    lines: usize,
+    arrival_time: Instant,
+}
+
+/// 受けた攻撃を受けた順に溜めておく構造体.
+pub struct GarbageQueue {
+    packets: VecDeque<AttackPacket>,
+    delay: Duration,
+}
+
+impl GarbageQueue {
+    /// 新規インスタンス作成.
+    pub fn new(delay: Duration) -> Self {
+        GarbageQueue {
+            packets: VecDeque::new(),
+            delay,
+        }
+    }
+
+    /// 攻撃を受けた.nowから遅延時間が経つと届く.
+    pub fn push(&mut self, lines: usize, now: Instant) {
+        if lines == 0 {
+            return;
+        }
+        self.packets.push_back(AttackPacket {
+            lines,
+            arrival_time: now + self.delay,
+        });
+    }
+
+    /// 自分が送るライン数で、溜まっている攻撃を古い順に相殺する.
+    /// 相殺しきれずに余ったライン数を返す.
+    pub fn cancel(&mut self, lines: usize) -> usize {
+        let mut remain = lines;
+        while remain > 0 {
+            let Some(packet) = self.packets.front_mut() else {
+                break;
+            };
+            if packet.lines > remain {
+                packet.lines -= remain;
+                remain = 0;
+            }
+            else {
+                remain -= packet.lines;
+                self.packets.pop_front();
+            }
+        }
+        remain
+    }
+
+    /// 届いた攻撃を取り出して、合計のライン数を返す.
+    pub fn pop_arrived(&mut self, now: Instant) -> usize {
+        let mut lines = 0;
+        while let Some(packet) = self.packets.front() {
+            if packet.arrival_time > now {
+                break;
+            }
+            lines += packet.lines;
+            self.packets.pop_front();
+        }
+        lines
+    }
+
+    /// まだ届いていない攻撃の合計ライン数を返す.
+    pub fn pending_lines(&self) -> usize {
+        self.packets.iter().map(|packet| packet.lines).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arrival_delay() {
+        let now = Instant::now();
+        let mut queue = GarbageQueue::new(Duration::from_millis(GARBAGE_DELAY_MILLIS));
+        queue.push(2, now);
+        queue.push(3, now + Duration::from_millis(500));
+        assert_eq!(queue.pending_lines(), 5);
+        // 届くまでは何も出てこない.
+        assert_eq!(queue.pop_arrived(now), 0);
+        assert_eq!(queue.pop_arrived(now + Duration::from_millis(GARBAGE_DELAY_MILLIS)), 2);
+        assert_eq!(queue.pop_arrived(now + Duration::from_millis(GARBAGE_DELAY_MILLIS + 500)), 3);
+        assert_eq!(queue.pending_lines(), 0);
+    }
+
+    #[test]
+    fn test_cancel() {
+        let now = Instant::now();
+        let mut queue = GarbageQueue::new(Duration::from_millis(GARBAGE_DELAY_MILLIS));
+        queue.push(2, now);
+        queue.push(3, now);
+        // 古い攻撃から相殺される.
+        assert_eq!(queue.cancel(3), 0);
+        assert_eq!(queue.pending_lines(), 2);
+        // 相殺しきれなかった分は送られる.
+        assert_eq!(queue.cancel(4), 2);
+        assert_eq!(queue.pending_lines(), 0);
+        assert_eq!(queue.cancel(1), 1);
+    }
+}
@@ -0,0 +1,36 @@
+//! プレイヤーごとの操作感の設定.
+//! 時間の単位は全てms.
+
+/// ARRをこの値にすると、リピートが始まった瞬間に壁まで移動する.
+pub const ARR_INSTANT: u32 = 0;
+/// ソフトドロップ倍率をこの値にすると、一瞬で一番下まで落ちる.
+pub const SOFT_DROP_INFINITE: u32 = 0;
+
+/// 操作感の設定.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct HandlingSettings {
+    /// 横移動キーを押し続けてから、リピートが始まるまでの時間(DAS).
+    pub das_ms: u32,
+    /// リピート中の横移動の間隔(ARR).0なら壁まで移動する.
+    pub arr_ms: u32,
+    /// ソフトドロップが自動落下の何倍速いか.0なら一番下まで落ちる.
+    pub soft_drop_factor: u32,
+    /// ブロックが出てから横移動のリピートを止めておく時間(DASカット).
+    pub das_cut_ms: u32,
+    /// 次のブロックにDASの溜めを持ち越すかどうか.
+    pub keep_das_charge: bool,
+}
+
+impl HandlingSettings {
+    /// 標準の設定.
+    /// ARRは、これまでの標準の7マス幅のフィールドを500msで端から端まで動く間隔(500 / 7)にしてある.
+    pub fn standard() -> Self {
+        HandlingSettings {
+            das_ms: 300,
+            arr_ms: 71,
+            soft_drop_factor: 20,
+            das_cut_ms: 0,
+            keep_das_charge: true,
+        }
+    }
+}
@@ -1,7 +1,7 @@
 //! キー入力のトレイト
 
-use std::time::Duration;
 use std::io;
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum KeyType {
     MenuDecide,
     MenuSelectUp,
@@ -14,18 +14,22 @@ pub enum KeyType {
     P1Right,
     P1Rotate,
     P1CounterRotate,
+    P1Rotate180,
     P1HardDrop,
     P1Hold,
     P1Pause,
+    P1ChangeTarget,
     P2Up,
     P2Down,
     P2Left,
     P2Right,
     P2Rotate,
     P2CounterRotate,
+    P2Rotate180,
     P2HardDrop,
     P2Hold,
     P2Pause,
+    P2ChangeTarget,
 }
 
 pub trait KeyInput{
@@ -33,5 +37,7 @@ pub trait KeyInput{
     fn is_press(&self, key: &KeyType) -> bool;
     fn is_down(&self, key: &KeyType) -> bool;
     fn is_up(&self, key: &KeyType) -> bool;
-    fn calc_elapsed(&self, key: &KeyType) -> Duration;
+    /// 新しい入力を読まずに次の更新に進む.1回の描画の間に何回か更新するときに使う.
+    /// 押し続けているキーはそのままで、押された瞬間と離された瞬間は前の更新で済んだものとする.
+    fn keep_input(&mut self);
 }
\ No newline at end of file
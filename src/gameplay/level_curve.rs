@@ -0,0 +1,74 @@
+//! レベルの上がり方と、レベルに応じた落下速度を定義する.
+
+/// 何ライン消したら1レベル上がるかの標準値.
+pub const DEFAULT_LINES_PER_LEVEL: u32 = 10;
+
+/// レベルごとの自動落下間隔(ms).インデックス0がレベル1.
+/// 後半は1ティック([crate::gameplay::game_clock::TICK_DURATION]、60ティック/秒で約16.7ms)より短くなるので、1ティックで複数段落ちる.
+/// 表の最後より上のレベルは最後の値を使い続ける.
+const GRAVITY_TABLE_MS: [u32; 20] = [
+    1000, 793, 618, 473, 355,
+    262, 190, 135, 94, 64,
+    43, 28, 18, 11, 7,
+    5, 4, 3, 2, 1,
+];
+
+/// レベルの進行を管理する構造体.
+pub struct LevelCurve {
+    start_level: u32,
+    lines_per_level: u32,
+}
+
+impl LevelCurve {
+    /// 新規インスタンス作成.
+    /// lines_per_levelに0が指定された場合はレベルが上がらない.
+    pub fn new(start_level: u32, lines_per_level: u32) -> Self {
+        LevelCurve {
+            start_level: start_level.max(1),
+            lines_per_level,
+        }
+    }
+
+    /// 消したライン数から現在のレベルを計算する.
+    pub fn calc_level(&self, erace_lines: u32) -> u32 {
+        if self.lines_per_level == 0 {
+            return self.start_level;
+        }
+        self.start_level + erace_lines / self.lines_per_level
+    }
+
+    /// レベルに応じた自動落下間隔(ms)を返す.
+    pub fn drop_speed_ms(level: u32) -> u32 {
+        let index = (level.max(1) - 1) as usize;
+        GRAVITY_TABLE_MS[index.min(GRAVITY_TABLE_MS.len() - 1)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calc_level() {
+        let curve = LevelCurve::new(1, DEFAULT_LINES_PER_LEVEL);
+        assert_eq!(curve.calc_level(0), 1);
+        assert_eq!(curve.calc_level(9), 1);
+        assert_eq!(curve.calc_level(10), 2);
+        assert_eq!(curve.calc_level(35), 4);
+
+        let curve = LevelCurve::new(5, 0);
+        assert_eq!(curve.calc_level(100), 5);
+    }
+
+    #[test]
+    fn test_drop_speed() {
+        assert_eq!(LevelCurve::drop_speed_ms(1), 1000);
+        assert_eq!(LevelCurve::drop_speed_ms(0), 1000);
+        // 後半は1フレームで複数段落ちる速さになる.
+        assert!(LevelCurve::drop_speed_ms(15) < 50 / 2);
+        assert_eq!(LevelCurve::drop_speed_ms(100), 1);
+        for level in 1..20 {
+            assert!(LevelCurve::drop_speed_ms(level) >= LevelCurve::drop_speed_ms(level + 1));
+        }
+    }
+}
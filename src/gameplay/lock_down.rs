@@ -0,0 +1,188 @@
+//! 接地したブロックをいつ固定するか(ロックダウン)の決まり.
+//! 段は下向きを正とするので、値が大きいほど下の段になる.
+use std::time::{Duration, Instant};
+
+/// ロックダウンの決まりのトレイト.
+/// ハードドロップはこの決まりに関係なくすぐに固定される.
+pub trait LockDownPolicy {
+    /// 新しいブロックを操作し始めた.rowは出現した段.
+    fn reset(&mut self, row: i32, now: Instant);
+    /// 移動か回転をした.
+    fn on_move(&mut self, move_count: u32, now: Instant);
+    /// 今いる段を伝える.
+    fn on_row(&mut self, row: i32, now: Instant);
+    /// 接地している場合に、もう固定するかどうかを返す.
+    fn should_lock(&self, now: Instant) -> bool;
+}
+
+/// 一番下の段を更新すると固定までの時間が延びる.
+/// reset_on_moveなら移動や回転でも延び、操作し続ければいつまでも固定されない.
+pub struct TimerLockDown {
+    lock_down_time: Duration,
+    reset_on_move: bool,
+    timer: Instant,
+    lowest_row: i32,
+}
+
+impl TimerLockDown {
+    /// 新規インスタンス作成.
+    pub fn new(lock_down_time: Duration, reset_on_move: bool) -> Self {
+        TimerLockDown {
+            lock_down_time,
+            reset_on_move,
+            timer: Instant::now(),
+            lowest_row: 0,
+        }
+    }
+}
+
+impl LockDownPolicy for TimerLockDown {
+    fn reset(&mut self, row: i32, now: Instant) {
+        self.timer = now;
+        self.lowest_row = row;
+    }
+
+    fn on_move(&mut self, _: u32, now: Instant) {
+        if self.reset_on_move {
+            self.timer = now;
+        }
+    }
+
+    fn on_row(&mut self, row: i32, now: Instant) {
+        if row > self.lowest_row {
+            self.lowest_row = row;
+            self.timer = now;
+        }
+    }
+
+    fn should_lock(&self, now: Instant) -> bool {
+        now.duration_since(self.timer) >= self.lock_down_time
+    }
+}
+
+/// 操作するたびに固定までの時間が延びるが、延ばせる回数に上限がある.
+/// 一番下の段を更新すると回数は元に戻る.
+pub struct MoveResetLockDown {
+    lock_down_time: Duration,
+    move_count_max: u32,
+    timer: Instant,
+    lowest_row: i32,
+    move_count: u32,
+}
+
+impl MoveResetLockDown {
+    /// 新規インスタンス作成.
+    pub fn new(lock_down_time: Duration, move_count_max: u32) -> Self {
+        MoveResetLockDown {
+            lock_down_time,
+            move_count_max,
+            timer: Instant::now(),
+            lowest_row: 0,
+            move_count: 0,
+        }
+    }
+}
+
+impl LockDownPolicy for MoveResetLockDown {
+    fn reset(&mut self, row: i32, now: Instant) {
+        self.timer = now;
+        self.lowest_row = row;
+        self.move_count = 0;
+    }
+
+    fn on_move(&mut self, move_count: u32, now: Instant) {
+        self.move_count += move_count;
+        self.timer = now;
+    }
+
+    fn on_row(&mut self, row: i32, now: Instant) {
+        if row > self.lowest_row {
+            self.lowest_row = row;
+            self.move_count = 0;
+            self.timer = now;
+        }
+    }
+
+    fn should_lock(&self, now: Instant) -> bool {
+        self.move_count >= self.move_count_max || now.duration_since(self.timer) >= self.lock_down_time
+    }
+}
+
+/// ルールごとに選べるロックダウンの種類.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LockDownType {
+    Infinite,
+    MoveReset,
+    StepReset,
+}
+
+impl LockDownType {
+    /// 対応するロックダウンの決まりを作る.move_count_maxは操作回数に上限がある場合のみ使う.
+    pub fn create(self, lock_down_time_ms: u32, move_count_max: u32) -> Box<dyn LockDownPolicy + Send> {
+        let lock_down_time = Duration::from_millis(lock_down_time_ms as u64);
+        match self {
+            LockDownType::Infinite => Box::new(TimerLockDown::new(lock_down_time, true)),
+            LockDownType::MoveReset => Box::new(MoveResetLockDown::new(lock_down_time, move_count_max)),
+            LockDownType::StepReset => Box::new(TimerLockDown::new(lock_down_time, false)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCK_DOWN_TIME: Duration = Duration::from_millis(500);
+
+    fn after(start: Instant, millis: u64) -> Instant {
+        start + Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_infinite_lock_down() {
+        let start = Instant::now();
+        let mut policy = TimerLockDown::new(LOCK_DOWN_TIME, true);
+        policy.reset(0, start);
+        // 操作し続ければ固定されない.
+        for i in 1..100 {
+            policy.on_move(1, after(start, i * 400));
+            assert!(!policy.should_lock(after(start, i * 400 + 400)));
+        }
+        assert!(policy.should_lock(after(start, 99 * 400 + 500)));
+    }
+
+    #[test]
+    fn test_move_reset_lock_down() {
+        let start = Instant::now();
+        let mut policy = MoveResetLockDown::new(LOCK_DOWN_TIME, 15);
+        policy.reset(0, start);
+        policy.on_move(1, after(start, 400));
+        assert!(!policy.should_lock(after(start, 800)));
+        assert!(policy.should_lock(after(start, 900)));
+        // 操作回数が上限に達するとすぐに固定.
+        policy.on_move(14, after(start, 900));
+        assert!(policy.should_lock(after(start, 900)));
+        // 下の段に落ちれば回数は戻る.
+        policy.on_row(1, after(start, 900));
+        assert!(!policy.should_lock(after(start, 900)));
+        // 上の段に戻っても戻らない.
+        policy.on_move(15, after(start, 900));
+        policy.on_row(0, after(start, 900));
+        assert!(policy.should_lock(after(start, 900)));
+    }
+
+    #[test]
+    fn test_step_reset_lock_down() {
+        let start = Instant::now();
+        let mut policy = TimerLockDown::new(LOCK_DOWN_TIME, false);
+        policy.reset(0, start);
+        // 操作しても時間は延びない.
+        policy.on_move(1, after(start, 400));
+        assert!(policy.should_lock(after(start, 500)));
+        // 新しい段に落ちると延びる.
+        policy.on_row(1, after(start, 400));
+        assert!(!policy.should_lock(after(start, 500)));
+        policy.on_row(1, after(start, 800));
+        assert!(policy.should_lock(after(start, 900)));
+    }
+}
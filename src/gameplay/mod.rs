@@ -1,9 +1,22 @@
 pub mod game_manager;
+pub mod game_clock;
 pub mod gameplay_manager;
 pub mod field;
+pub mod garbage_queue;
+pub mod game_rules;
 pub mod controller;
+pub mod handling_settings;
+pub mod player_profile;
+pub mod replay;
 pub mod block;
+pub mod clear_event;
+pub mod gameplay_event;
 pub mod t_spin_checker;
+pub mod spin_checker;
+pub mod visibility;
 pub mod score_calculator;
 pub mod key_input;
+pub mod level_curve;
+pub mod lock_down;
+pub mod targeting;
 pub mod game_renderer_sender;
\ No newline at end of file
@@ -0,0 +1,124 @@
+//! プレイヤーごとのプロフィール.操作感の設定をファイルに保存しておく.
+//! ファイルはブロック定義と同じく'キー = 値'の形式.
+
+use crate::gameplay::handling_settings::HandlingSettings;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// プロフィールを保存するディレクトリ.
+const PROFILE_DIR: &str = "profiles";
+/// プロフィールを持てるプレイヤーの数.1Pと2P.
+pub const PROFILE_COUNT: usize = 2;
+
+/// プレイヤーのプロフィール.
+#[derive(Clone, PartialEq, Debug)]
+pub struct PlayerProfile {
+    pub name: String,
+    pub handling: HandlingSettings,
+}
+
+impl PlayerProfile {
+    /// 標準設定のプロフィールを作成する.
+    pub fn new(name: &str) -> Self {
+        PlayerProfile {
+            name: name.to_string(),
+            handling: HandlingSettings::standard(),
+        }
+    }
+
+    /// プロフィールの内容を読み込む.書かれていない項目は標準設定のまま.
+    /// 不正な内容であれば、何行目がおかしいかを含めたメッセージを返す.
+    pub fn parse(default_name: &str, text: &str) -> Result<Self, String> {
+        let mut profile = PlayerProfile::new(default_name);
+        for (index, raw_line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = raw_line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line.split_once('=')
+                .ok_or(format!("{}行目: 'キー = 値' の形式ではありません", line_number))?;
+            let (key, value) = (key.trim(), value.trim());
+            let handling = &mut profile.handling;
+            let result = match key {
+                "name" => {
+                    profile.name = value.to_string();
+                    Ok(())
+                },
+                "das" => parse_number(value).map(|number| handling.das_ms = number),
+                "arr" => parse_number(value).map(|number| handling.arr_ms = number),
+                "soft_drop_factor" => parse_number(value).map(|number| handling.soft_drop_factor = number),
+                "das_cut" => parse_number(value).map(|number| handling.das_cut_ms = number),
+                "keep_das_charge" => value.parse::<bool>()
+                    .map(|keep| handling.keep_das_charge = keep)
+                    .map_err(|_| format!("trueかfalseで指定してください: {}", value)),
+                _ => Err(format!("不明なキーです: {}", key)),
+            };
+            result.map_err(|message| format!("{}行目: {}", line_number, message))?;
+        }
+        Ok(profile)
+    }
+
+    /// ファイルに書き出す形式にする.
+    pub fn to_text(&self) -> String {
+        format!("name = {}\ndas = {}\narr = {}\nsoft_drop_factor = {}\ndas_cut = {}\nkeep_das_charge = {}\n",
+            self.name, self.handling.das_ms, self.handling.arr_ms, self.handling.soft_drop_factor,
+            self.handling.das_cut_ms, self.handling.keep_das_charge)
+    }
+}
+
+/// 0以上の整数を読み込む.
+fn parse_number(value: &str) -> Result<u32, String> {
+    value.parse::<u32>().map_err(|_| format!("0以上の整数で指定してください: {}", value))
+}
+
+/// index番目のプレイヤーのプロフィールのパス.
+fn profile_path(index: usize) -> PathBuf {
+    Path::new(PROFILE_DIR).join(format!("player{}.txt", index + 1))
+}
+
+/// 全プレイヤーのプロフィールを読み込む.
+/// ファイルが無い、または読めない場合は標準設定にする.プロフィールのせいで遊べなくなるのは避けたいため.
+pub fn load_profiles() -> Vec<PlayerProfile> {
+    (0..PROFILE_COUNT).map(|index| {
+        let default_name = format!("{}P", index + 1);
+        fs::read_to_string(profile_path(index)).ok()
+            .and_then(|text| PlayerProfile::parse(&default_name, &text).ok())
+            .unwrap_or_else(|| PlayerProfile::new(&default_name))
+    }).collect()
+}
+
+/// index番目のプレイヤーのプロフィールを保存する.
+pub fn save_profile(index: usize, profile: &PlayerProfile) -> Result<(), String> {
+    fs::create_dir_all(PROFILE_DIR).map_err(|error| format!("{}: {}", PROFILE_DIR, error))?;
+    let path = profile_path(index);
+    fs::write(&path, profile.to_text()).map_err(|error| format!("{}: {}", path.display(), error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let profile = PlayerProfile::parse("1P", "# コメント\ndas = 120\narr = 0\nkeep_das_charge = false\n").unwrap();
+        assert_eq!(profile.name, "1P");
+        assert_eq!(profile.handling.das_ms, 120);
+        assert_eq!(profile.handling.arr_ms, 0);
+        assert!(!profile.handling.keep_das_charge);
+        // 書かれていない項目は標準設定.
+        assert_eq!(profile.handling.soft_drop_factor, HandlingSettings::standard().soft_drop_factor);
+
+        assert!(PlayerProfile::parse("1P", "das = -1\n").is_err());
+        assert!(PlayerProfile::parse("1P", "das\n").is_err());
+        assert!(PlayerProfile::parse("1P", "speed = 1\n").is_err());
+    }
+
+    #[test]
+    fn test_to_text() {
+        let mut profile = PlayerProfile::new("2P");
+        profile.handling.soft_drop_factor = 0;
+        profile.handling.das_cut_ms = 30;
+        assert_eq!(PlayerProfile::parse("x", &profile.to_text()), Ok(profile));
+    }
+}
@@ -0,0 +1,351 @@
+//! リプレイの記録と再生.
+//! シードと設定、プレイヤーがフレームごとに行った操作を残しておき、同じ操作を流し直してゲームを再現する.
+//! ファイルはプロフィールと同じく'キー = 値'の形式で、最初の行にバージョンを書く.
+
+use crate::gameplay::{
+    block::{
+        control_block::ControlBlock, hold_block::HoldBlock, next_blocks::{NextBlocks, RandomizerType},
+        rotation_system::RotationSystemType,
+    },
+    controller::{self, PlayController},
+    field::{Field, GravityType},
+    game_manager::FieldSize,
+    game_rules::GameRulesType,
+    gameplay_manager::PlayerType,
+    visibility::VisibilityType,
+};
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// リプレイファイルの形式のバージョン.形式か、1フレームの長さを変えたら上げる.
+/// 2から1フレームは1ティック(1/60秒).
+pub const REPLAY_VERSION: u32 = 2;
+/// リプレイを保存するディレクトリ.
+const REPLAY_DIR: &str = "replays";
+
+/// 記録する操作.プレイヤーが実際にブロックに加えられた操作だけを残す.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ReplayAction {
+    Left,
+    Right,
+    SoftDrop,
+    Rotate,
+    CounterRotate,
+    Rotate180,
+    HardDrop,
+    Hold,
+    ChangeTarget,
+}
+
+const REPLAY_ACTIONS: [ReplayAction; 9] = [
+    ReplayAction::Left, ReplayAction::Right, ReplayAction::SoftDrop,
+    ReplayAction::Rotate, ReplayAction::CounterRotate, ReplayAction::Rotate180,
+    ReplayAction::HardDrop, ReplayAction::Hold, ReplayAction::ChangeTarget,
+];
+const FIELD_SIZES: [FieldSize; 4] = [FieldSize::Standard, FieldSize::Wide, FieldSize::Narrow, FieldSize::Tall];
+const ROTATION_SYSTEMS: [RotationSystemType; 3] = [RotationSystemType::Srs, RotationSystemType::Classic, RotationSystemType::Ars];
+const RANDOMIZERS: [RandomizerType; 4] = [RandomizerType::Bag, RandomizerType::DoubleBag, RandomizerType::Random, RandomizerType::History];
+const RULES: [GameRulesType; 4] = [GameRulesType::Standard, GameRulesType::ModernFast, GameRulesType::ClassicSlow, GameRulesType::Practice];
+const VISIBILITIES: [VisibilityType; 3] = [VisibilityType::Normal, VisibilityType::Fading, VisibilityType::Invisible];
+const GRAVITIES: [GravityType; 3] = [GravityType::Naive, GravityType::Sticky, GravityType::Cascade];
+/// リプレイに残せるのは有人プレイヤーだけ.
+const CONTROLLERS: [PlayerType; 2] = [PlayerType::Player1, PlayerType::Player2];
+
+/// 1フレームで行われた操作.行われた順に並ぶ.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ReplayFrame {
+    pub frame: u64,
+    pub actions: Vec<ReplayAction>,
+}
+
+/// ゲームを作り直すための設定.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ReplaySettings {
+    pub seed: u64,
+    pub level: u32,
+    pub field_size: FieldSize,
+    pub big: bool,
+    pub rotation_system: RotationSystemType,
+    /// ブロックセットの名前.
+    pub piece_set: String,
+    pub randomizer: RandomizerType,
+    pub rules: GameRulesType,
+    pub visibility: VisibilityType,
+    pub gravity: GravityType,
+    pub controller: PlayerType,
+}
+
+/// 1ゲーム分のリプレイ.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Replay {
+    pub settings: ReplaySettings,
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    /// 新規インスタンス作成.
+    pub fn new(settings: ReplaySettings, frames: Vec<ReplayFrame>) -> Self {
+        Replay { settings, frames }
+    }
+
+    /// リプレイの内容を読み込む.設定は全て書かれている必要がある.
+    /// 不正な内容であれば、何行目がおかしいかを含めたメッセージを返す.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut version = None;
+        let mut values: Vec<(&str, &str, usize)> = vec![];
+        let mut frames: Vec<ReplayFrame> = vec![];
+        for (index, raw_line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = raw_line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line.split_once('=')
+                .ok_or(format!("{}行目: 'キー = 値' の形式ではありません", line_number))?;
+            let (key, value) = (key.trim(), value.trim());
+            // バージョンが分かるまでは他の行を読まない.形式が違うかもしれないため.
+            if version.is_none() {
+                if key != "version" {
+                    return Err(format!("{}行目: 最初にversionを指定してください", line_number));
+                }
+                let number = value.parse::<u32>()
+                    .map_err(|_| format!("{}行目: 0以上の整数で指定してください: {}", line_number, value))?;
+                if number != REPLAY_VERSION {
+                    return Err(format!("{}行目: 対応していないバージョンです: {}", line_number, number));
+                }
+                version = Some(number);
+                continue;
+            }
+            if key == "frame" {
+                let frame = parse_frame(value).map_err(|message| format!("{}行目: {}", line_number, message))?;
+                if frames.last().is_some_and(|last| last.frame >= frame.frame) {
+                    return Err(format!("{}行目: フレームが順番に並んでいません: {}", line_number, frame.frame));
+                }
+                frames.push(frame);
+                continue;
+            }
+            values.push((key, value, line_number));
+        }
+        if version.is_none() {
+            return Err(String::from("versionが指定されていません"));
+        }
+        let mut settings = SettingsReader { values };
+        let replay_settings = ReplaySettings {
+            seed: settings.read("seed", |value| value.parse::<u64>().map_err(|_| format!("0以上の整数で指定してください: {}", value)))?,
+            level: settings.read("level", |value| value.parse::<u32>().map_err(|_| format!("0以上の整数で指定してください: {}", value)))?,
+            field_size: settings.read("field_size", |value| parse_choice(value, &FIELD_SIZES))?,
+            big: settings.read("big", |value| value.parse::<bool>().map_err(|_| format!("trueかfalseで指定してください: {}", value)))?,
+            rotation_system: settings.read("rotation_system", |value| parse_choice(value, &ROTATION_SYSTEMS))?,
+            piece_set: settings.read("piece_set", |value| Ok(value.to_string()))?,
+            randomizer: settings.read("randomizer", |value| parse_choice(value, &RANDOMIZERS))?,
+            rules: settings.read("rules", |value| parse_choice(value, &RULES))?,
+            visibility: settings.read("visibility", |value| parse_choice(value, &VISIBILITIES))?,
+            gravity: settings.read("gravity", |value| parse_choice(value, &GRAVITIES))?,
+            controller: settings.read("controller", |value| parse_choice(value, &CONTROLLERS))?,
+        };
+        if let Some((key, _, line_number)) = settings.values.first() {
+            return Err(format!("{}行目: 不明なキーです: {}", line_number, key));
+        }
+        Ok(Replay::new(replay_settings, frames))
+    }
+
+    /// ファイルに書き出す形式にする.
+    pub fn to_text(&self) -> String {
+        let settings = &self.settings;
+        let mut text = format!("version = {}\nseed = {}\nlevel = {}\nfield_size = {:?}\nbig = {}\nrotation_system = {:?}\npiece_set = {}\nrandomizer = {:?}\nrules = {:?}\nvisibility = {:?}\ngravity = {:?}\ncontroller = {:?}\n",
+            REPLAY_VERSION, settings.seed, settings.level, settings.field_size, settings.big, settings.rotation_system,
+            settings.piece_set, settings.randomizer, settings.rules, settings.visibility, settings.gravity, settings.controller);
+        for frame in &self.frames {
+            let actions: Vec<String> = frame.actions.iter().map(|action| format!("{:?}", action)).collect();
+            text += &format!("frame = {} {}\n", frame.frame, actions.join(" "));
+        }
+        text
+    }
+
+    /// リプレイファイルを読み込む.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+        Replay::parse(&text).map_err(|message| format!("{}: {}", path.display(), message))
+    }
+
+    /// リプレイを保存するディレクトリに、保存した時刻の名前で書き出す.保存したパスを返す.
+    pub fn save(&self) -> Result<PathBuf, String> {
+        fs::create_dir_all(REPLAY_DIR).map_err(|error| format!("{}: {}", REPLAY_DIR, error))?;
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0);
+        let path = Path::new(REPLAY_DIR).join(format!("replay_{}.txt", seconds));
+        fs::write(&path, self.to_text()).map_err(|error| format!("{}: {}", path.display(), error))?;
+        Ok(path)
+    }
+}
+
+/// 読み込んだ設定の行から、キーを指定して取り出す.取り出した行は消す.
+struct SettingsReader<'a> {
+    values: Vec<(&'a str, &'a str, usize)>,
+}
+
+impl SettingsReader<'_> {
+    fn read<T>(&mut self, key: &str, parse: impl Fn(&str) -> Result<T, String>) -> Result<T, String> {
+        let index = self.values.iter().position(|(value_key, _, _)| *value_key == key)
+            .ok_or(format!("{}が指定されていません", key))?;
+        let (_, value, line_number) = self.values.remove(index);
+        parse(value).map_err(|message| format!("{}行目: {}", line_number, message))
+    }
+}
+
+/// 'フレーム番号 操作 操作 …'の形式を読み込む.
+fn parse_frame(value: &str) -> Result<ReplayFrame, String> {
+    let mut words = value.split_whitespace();
+    let frame_str = words.next().ok_or("フレーム番号を指定してください")?;
+    let frame = frame_str.parse::<u64>().map_err(|_| format!("0以上の整数で指定してください: {}", frame_str))?;
+    let actions = words.map(|word| parse_choice(word, &REPLAY_ACTIONS)).collect::<Result<Vec<_>, _>>()?;
+    if actions.is_empty() {
+        return Err(String::from("操作が指定されていません"));
+    }
+    Ok(ReplayFrame { frame, actions })
+}
+
+/// 書き出したときの名前から値を選ぶ.
+fn parse_choice<T: Copy + Debug>(value: &str, choices: &[T]) -> Result<T, String> {
+    choices.iter().copied()
+        .find(|choice| format!("{:?}", choice) == value)
+        .ok_or(format!("不明な値です: {}", value))
+}
+
+/// 記録した操作をそのまま流し直すコントローラー.キー入力は使わない.
+pub struct ReplayController {
+    frames: VecDeque<ReplayFrame>,
+    actions: Vec<ReplayAction>,
+}
+
+impl ReplayController {
+    /// 新規インスタンス作成.
+    pub fn new(frames: Vec<ReplayFrame>) -> Self {
+        ReplayController {
+            frames: frames.into(),
+            actions: vec![],
+        }
+    }
+
+    /// このフレームの操作を順に行う.
+    /// 変更回数はプレイヤーが操作したときと同じように数える.
+    fn apply_actions(&mut self, target: &mut ControlBlock, hold_block: &mut HoldBlock, field: &Field, next_blocks: &mut NextBlocks) -> i32 {
+        let mut move_count = 0;
+        for action in std::mem::take(&mut self.actions) {
+            match action {
+                ReplayAction::Left => if target.left(field) { move_count += 1 },
+                ReplayAction::Right => if target.right(field) { move_count += 1 },
+                ReplayAction::SoftDrop => { target.soft_drop(field); },
+                ReplayAction::Rotate => if target.rotate(field) { move_count += 1 },
+                ReplayAction::CounterRotate => if target.counter_rotate(field) { move_count += 1 },
+                ReplayAction::Rotate180 => if target.rotate_180(field) { move_count += 1 },
+                ReplayAction::HardDrop => {
+                    target.hard_drop(field);
+                    move_count += controller::HARD_DROP_MOVE_COUNT;
+                },
+                ReplayAction::Hold => move_count = controller::apply_hold(target, hold_block, field, next_blocks),
+                ReplayAction::ChangeTarget => {},
+            }
+        }
+        move_count
+    }
+}
+
+impl PlayController for ReplayController {
+    /// このフレームで行う操作を取り出す.
+    fn begin_frame(&mut self, frame: u64) {
+        self.actions.clear();
+        while self.frames.front().is_some_and(|replay_frame| replay_frame.frame < frame) {
+            self.frames.pop_front();
+        }
+        if self.frames.front().is_some_and(|replay_frame| replay_frame.frame == frame) {
+            self.actions = self.frames.pop_front().map(|replay_frame| replay_frame.actions).unwrap_or_default();
+        }
+    }
+
+    fn spawn_control(&mut self, target: &mut ControlBlock, hold_block: &mut HoldBlock, field: &Field, next_blocks: &mut NextBlocks) {
+        self.apply_actions(target, hold_block, field, next_blocks);
+    }
+
+    fn control(&mut self, target: &mut ControlBlock, hold_block: &mut HoldBlock, field: &Field, next_blocks: &mut NextBlocks, _: u128, _: u32) -> i32 {
+        self.apply_actions(target, hold_block, field, next_blocks)
+    }
+
+    /// リプレイ中はポーズしない.
+    fn is_pause_requested(&self) -> bool {
+        false
+    }
+
+    fn is_target_change_requested(&self) -> bool {
+        self.actions.contains(&ReplayAction::ChangeTarget)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::controller::test_support::ControlFixture;
+    use crate::gameplay::field::FieldConfig;
+
+    fn make_replay() -> Replay {
+        let settings = ReplaySettings {
+            seed: 1234,
+            level: 1,
+            field_size: FieldSize::Wide,
+            big: false,
+            rotation_system: RotationSystemType::Srs,
+            piece_set: String::from("テトロミノ"),
+            randomizer: RandomizerType::Bag,
+            rules: GameRulesType::ModernFast,
+            visibility: VisibilityType::Normal,
+            gravity: GravityType::Cascade,
+            controller: PlayerType::Player1,
+        };
+        Replay::new(settings, vec![
+            ReplayFrame { frame: 3, actions: vec![ReplayAction::Left, ReplayAction::Rotate] },
+            ReplayFrame { frame: 10, actions: vec![ReplayAction::HardDrop] },
+        ])
+    }
+
+    #[test]
+    fn test_to_text() {
+        let replay = make_replay();
+        assert_eq!(Replay::parse(&replay.to_text()), Ok(replay));
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let text = make_replay().to_text();
+        // バージョンが違うものは読まない.
+        let version_line = format!("version = {}\n", REPLAY_VERSION);
+        assert!(Replay::parse(&text.replacen(&version_line, "version = 1\n", 1)).is_err());
+        assert!(Replay::parse(&text.replacen(&version_line, "", 1)).is_err());
+        // 設定が足りないものや、知らない値は読まない.
+        assert!(Replay::parse(&text.replacen("seed = 1234\n", "", 1)).is_err());
+        assert!(Replay::parse(&text.replacen("Srs", "Unknown", 1)).is_err());
+        assert!(Replay::parse(&(text.clone() + "speed = 1\n")).is_err());
+        // フレームは順番に並んでいる必要がある.
+        assert!(Replay::parse(&(text.clone() + "frame = 5 Left\n")).is_err());
+        assert!(Replay::parse(&(text + "frame = 20\n")).is_err());
+    }
+
+    #[test]
+    fn test_replay_controller() {
+        let mut fixture = ControlFixture::new(FieldConfig::standard());
+        let spawn_x = fixture.target.position.x;
+        let mut controller = ReplayController::new(make_replay().frames);
+
+        // 記録のないフレームでは何もしない.
+        controller.begin_frame(0);
+        assert_eq!(fixture.control(&mut controller), 0);
+        controller.begin_frame(3);
+        assert_eq!(fixture.control(&mut controller), 2);
+        assert_eq!(fixture.target.position.x, spawn_x - 1);
+        // 同じ操作は2回行わない.
+        assert_eq!(fixture.control(&mut controller), 0);
+        controller.begin_frame(10);
+        assert!(fixture.control(&mut controller) >= controller::HARD_DROP_MOVE_COUNT);
+    }
+}
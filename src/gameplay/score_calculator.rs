@@ -2,35 +2,79 @@
 //! スコアの計算をする.
 //! ゲームモードに応じて計算方法を変えてもいいかも.
 
-use crate::gameplay::t_spin_checker::TSpinType;
+use crate::gameplay::{clear_event::ClearEvent, t_spin_checker::TSpinType};
 /// スコア計算機のトレイト
 pub trait ScoreCalculator {
-    fn calc(&self, eraced_lines: u32, t_spin: TSpinType, combos: u32) -> u64;
+    /// ブロックを固定したときのスコア.levelは消す前のレベル.
+    fn calc(&self, event: &ClearEvent, level: u32) -> u64;
+    /// ソフトドロップとハードドロップで落としたマス数によるスコア.
+    fn calc_drop(&self, soft_drop_cells: u32, hard_drop_cells: u32) -> u64;
 }
 
-/// シンプルなスコア計算のための構造体.
-pub struct SimpleScoreCalculator {}
-impl SimpleScoreCalculator {
+/// 消したライン数ごとの基本点.表より多い場合は最後の値を使う.
+const LINE_CLEAR_SCORES: [u64; 5] = [0, 100, 300, 500, 800];
+/// Tスピンミニの基本点.
+const T_SPIN_MINI_SCORES: [u64; 3] = [100, 200, 400];
+/// Tスピンの基本点.
+const T_SPIN_FULL_SCORES: [u64; 4] = [400, 800, 1200, 1600];
+/// Tブロック以外のスピンの基本点.Tスピンミニより高く、Tスピンより低い.
+const OTHER_SPIN_SCORES: [u64; 4] = [100, 300, 600, 1000];
+/// 全消しの追加点.
+const PERFECT_CLEAR_SCORES: [u64; 5] = [0, 800, 1200, 1800, 2000];
+/// 連鎖の段数ごとの追加点.
+const CHAIN_SCORES: [u64; 6] = [0, 200, 400, 800, 1200, 1600];
+/// コンボ1回あたりの追加点.
+const COMBO_SCORE: u64 = 50;
+/// ソフトドロップ1マスあたりの点数.
+const SOFT_DROP_SCORE: u64 = 1;
+/// ハードドロップ1マスあたりの点数.
+const HARD_DROP_SCORE: u64 = 2;
+
+/// 表を引く.表より大きい場合は最後の値.
+fn lookup_score(table: &[u64], index: u32) -> u64 {
+    table[(index as usize).min(table.len() - 1)]
+}
+
+/// 消し方、レベル、コンボ、バックトゥバック、全消し、ドロップを考慮したスコア計算.
+pub struct StandardScoreCalculator {}
+impl StandardScoreCalculator {
     /// 新規インスタンス作成.
     pub fn new() -> Self {
-        SimpleScoreCalculator {  }
+        StandardScoreCalculator {  }
     }
 }
-impl ScoreCalculator for SimpleScoreCalculator {
-    /// スコア計算.コンボ数は考慮しない.
-    fn calc(&self, eraced_lines: u32, t_spin: TSpinType, _: u32) -> u64 {
-        // とりあえず1行100点で実装.Tスピンなら2倍
-        let multiplier: u64 = match t_spin { 
-            TSpinType::Full => 3,
-            TSpinType::Mini => 2,
-            TSpinType::None => 1
+impl ScoreCalculator for StandardScoreCalculator {
+    /// スコア計算.消し方の点数はレベル倍し、バックトゥバックならさらに1.5倍する.
+    fn calc(&self, event: &ClearEvent, level: u32) -> u64 {
+        let level = level.max(1) as u64;
+        let base = match event.t_spin {
+            TSpinType::Full => lookup_score(&T_SPIN_FULL_SCORES, event.lines),
+            TSpinType::Mini => lookup_score(&T_SPIN_MINI_SCORES, event.lines),
+            TSpinType::Other => lookup_score(&OTHER_SPIN_SCORES, event.lines),
+            TSpinType::None => lookup_score(&LINE_CLEAR_SCORES, event.lines),
         };
-        eraced_lines as u64 * 100 as u64 * multiplier
+        let mut score = base * level;
+        if event.back_to_back {
+            score = score * 3 / 2;
+        }
+        // 2回目の連続消去からコンボの点数が入る.
+        score += COMBO_SCORE * event.combo.saturating_sub(1) as u64 * level;
+        if event.perfect_clear {
+            score += lookup_score(&PERFECT_CLEAR_SCORES, event.lines) * level;
+        }
+        score += lookup_score(&CHAIN_SCORES, event.chain) * level;
+        score
+    }
+
+    /// ドロップの点数.ハードドロップはソフトドロップの2倍.
+    fn calc_drop(&self, soft_drop_cells: u32, hard_drop_cells: u32) -> u64 {
+        soft_drop_cells as u64 * SOFT_DROP_SCORE + hard_drop_cells as u64 * HARD_DROP_SCORE
     }
 }
 
+/// 攻撃力計算機のトレイト.
 pub trait AttackPowerCalculator {
-    fn calc(&self, eraced_lines: u32, t_spin: TSpinType, combo: u32) -> usize;
+    fn calc(&self, event: &ClearEvent) -> usize;
 }
 
 pub struct SimpleAttackPowerCalculator {}
@@ -41,24 +85,147 @@ impl SimpleAttackPowerCalculator {
 }
 impl AttackPowerCalculator for SimpleAttackPowerCalculator {
     /// 攻撃ライン数計算.消したラインをそのまま送ることにする.
-    fn calc(&self, eraced_lines:u32, _: TSpinType, _:u32) -> usize {
-        eraced_lines as usize
+    fn calc(&self, event: &ClearEvent) -> usize {
+        event.lines as usize
+    }
+}
+
+/// 攻撃表.各表のインデックスは消したライン数やコンボ数で、表より大きい場合は最後の値を使う.
+pub struct AttackTable {
+    /// 通常の消去.
+    pub lines: Vec<usize>,
+    /// Tスピンミニでの消去.
+    pub t_spin_mini: Vec<usize>,
+    /// Tスピンでの消去.
+    pub t_spin_full: Vec<usize>,
+    /// Tブロック以外のスピンでの消去.
+    pub other_spin: Vec<usize>,
+    /// コンボ数ごとの追加分.
+    pub combo: Vec<usize>,
+    /// バックトゥバックの追加分.
+    pub back_to_back: usize,
+    /// 全消しの追加分.
+    pub perfect_clear: usize,
+    /// 連鎖の段数ごとの追加分.
+    pub chain: Vec<usize>,
+}
+
+impl AttackTable {
+    /// 対戦でよく使われる標準的な攻撃表.
+    pub fn standard() -> Self {
+        AttackTable {
+            lines: vec![0, 0, 1, 2, 4],
+            t_spin_mini: vec![0, 0, 1],
+            t_spin_full: vec![0, 2, 4, 6],
+            other_spin: vec![0, 1, 2, 4],
+            combo: vec![0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5],
+            back_to_back: 1,
+            perfect_clear: 10,
+            chain: vec![0, 1, 2, 2, 3, 3, 4],
+        }
+    }
+
+    /// 表を引く.表より大きい場合は最後の値、空なら0.
+    fn lookup(table: &[usize], index: usize) -> usize {
+        table.get(index).or(table.last()).copied().unwrap_or(0)
+    }
+}
+
+/// 攻撃表を使った対戦向けの攻撃力計算.
+pub struct CompetitiveAttackPowerCalculator {
+    table: AttackTable,
+}
+impl CompetitiveAttackPowerCalculator {
+    pub fn new(table: AttackTable) -> Self {
+        CompetitiveAttackPowerCalculator { table }
+    }
+}
+impl AttackPowerCalculator for CompetitiveAttackPowerCalculator {
+    /// 攻撃ライン数計算.消し方ごとの値に、コンボ、バックトゥバック、全消し、連鎖の分を足す.
+    fn calc(&self, event: &ClearEvent) -> usize {
+        if event.lines == 0 {
+            return 0;
+        }
+        let line_table = match event.t_spin {
+            TSpinType::Full => &self.table.t_spin_full,
+            TSpinType::Mini => &self.table.t_spin_mini,
+            TSpinType::Other => &self.table.other_spin,
+            TSpinType::None => &self.table.lines,
+        };
+        let mut attack_power = AttackTable::lookup(line_table, event.lines as usize);
+        attack_power += AttackTable::lookup(&self.table.combo, event.combo as usize);
+        if event.back_to_back {
+            attack_power += self.table.back_to_back;
+        }
+        if event.perfect_clear {
+            attack_power += self.table.perfect_clear;
+        }
+        attack_power += AttackTable::lookup(&self.table.chain, event.chain as usize);
+        attack_power
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::gameplay::block::block_datas::BlockType;
+
+    fn event(lines: u32, t_spin: TSpinType, combo: u32, back_to_back: bool, perfect_clear: bool) -> ClearEvent {
+        let spin_block = match t_spin {
+            TSpinType::None => BlockType::None,
+            TSpinType::Other => BlockType::L,
+            _ => BlockType::T,
+        };
+        ClearEvent { lines, t_spin, spin_block, combo, back_to_back, perfect_clear, chain: 0 }
+    }
+
+    fn chain_event(lines: u32, chain: u32) -> ClearEvent {
+        ClearEvent { chain, ..event(lines, TSpinType::None, 0, false, false) }
+    }
 
     #[test]
-    fn test_calc_score() {
-        let calculator = SimpleScoreCalculator::new();
-        assert_eq!(calculator.calc(4, TSpinType::Full, 0), 1200);
+    fn test_calc_standard_score() {
+        let calculator = StandardScoreCalculator::new();
+        assert_eq!(calculator.calc(&event(1, TSpinType::None, 1, false, false), 1), 100);
+        // レベル倍になる.
+        assert_eq!(calculator.calc(&event(4, TSpinType::None, 1, false, false), 3), 2400);
+        // ラインを消さないTスピンにも点数が入る.
+        assert_eq!(calculator.calc(&event(0, TSpinType::Full, 0, false, false), 1), 400);
+        assert_eq!(calculator.calc(&event(0, TSpinType::None, 0, false, false), 5), 0);
+        assert_eq!(calculator.calc(&event(2, TSpinType::Full, 1, true, false), 1), 1800);
+        assert_eq!(calculator.calc(&event(1, TSpinType::None, 3, false, false), 2), 400);
+        assert_eq!(calculator.calc(&event(2, TSpinType::None, 1, false, true), 1), 1500);
+        assert_eq!(calculator.calc(&chain_event(1, 2), 2), 1000);
+        // Tブロック以外のスピンはTスピンミニとは別の点数.
+        assert_eq!(calculator.calc(&event(2, TSpinType::Other, 1, false, false), 1), 600);
+        assert_eq!(calculator.calc(&event(2, TSpinType::Mini, 1, false, false), 1), 400);
+        assert_eq!(calculator.calc_drop(10, 5), 20);
     }
 
     #[test]
     fn test_calc_attack_power() {
         let calculator = SimpleAttackPowerCalculator::new();
-        assert_eq!(calculator.calc(2, TSpinType::Full, 2), 2);
+        assert_eq!(calculator.calc(&event(2, TSpinType::Full, 2, true, false)), 2);
+    }
+
+    #[test]
+    fn test_calc_competitive_attack_power() {
+        let calculator = CompetitiveAttackPowerCalculator::new(AttackTable::standard());
+        // 1ラインだけでは攻撃にならない.
+        assert_eq!(calculator.calc(&event(1, TSpinType::None, 1, false, false)), 0);
+        assert_eq!(calculator.calc(&event(4, TSpinType::None, 1, false, false)), 4);
+        // 表より多く消しても最後の値になる.
+        assert_eq!(calculator.calc(&event(5, TSpinType::None, 1, false, false)), 4);
+        assert_eq!(calculator.calc(&event(2, TSpinType::Full, 1, false, false)), 4);
+        assert_eq!(calculator.calc(&event(2, TSpinType::Mini, 1, false, false)), 1);
+        assert_eq!(calculator.calc(&event(2, TSpinType::Other, 1, false, false)), 2);
+        // コンボ、バックトゥバック、全消しは足される.
+        assert_eq!(calculator.calc(&event(1, TSpinType::None, 3, false, false)), 1);
+        assert_eq!(calculator.calc(&event(2, TSpinType::Full, 1, true, false)), 5);
+        assert_eq!(calculator.calc(&event(2, TSpinType::None, 1, false, true)), 11);
+        assert_eq!(calculator.calc(&event(0, TSpinType::Full, 0, true, false)), 0);
+        // 連鎖の分も足される.
+        assert_eq!(calculator.calc(&chain_event(2, 1)), 2);
+        assert_eq!(calculator.calc(&chain_event(1, 10)), 4);
     }
 }
\ No newline at end of file
@@ -0,0 +1,126 @@
+//! スピンの判定.
+//! Tブロックだけを判定するか、全てのブロックを判定するかをルールで選ぶ.
+
+use crate::gameplay::block::{block_datas::BlockType, control_block::ControlBlock};
+use crate::gameplay::field::Field;
+use crate::gameplay::t_spin_checker::{TSpinChecker, TSpinType};
+
+/// スピン判定のトレイト.
+pub trait SpinChecker {
+    /// 最後の操作がスピンだったかを判定する.
+    fn calc_spin_type(&self, control_block: &ControlBlock, field: &Field) -> TSpinType;
+}
+
+/// Tブロックだけをスピンとして判定する.これまでの判定.
+pub struct TSpinOnlyChecker {
+    t_spin_checker: TSpinChecker,
+}
+
+impl TSpinOnlyChecker {
+    /// 新規インスタンス作成.
+    pub fn new() -> Self {
+        TSpinOnlyChecker {
+            t_spin_checker: TSpinChecker::new(),
+        }
+    }
+}
+
+impl SpinChecker for TSpinOnlyChecker {
+    fn calc_spin_type(&self, control_block: &ControlBlock, field: &Field) -> TSpinType {
+        self.t_spin_checker.calc_t_spin_type(control_block, field)
+    }
+}
+
+/// 全てのブロックをスピンとして判定する.
+/// Tブロックは四隅で判定し、それ以外は回転した後に左右にも上にも動かせなければスピンとする.
+pub struct AllSpinChecker {
+    t_spin_checker: TSpinChecker,
+}
+
+impl AllSpinChecker {
+    /// 新規インスタンス作成.
+    pub fn new() -> Self {
+        AllSpinChecker {
+            t_spin_checker: TSpinChecker::new(),
+        }
+    }
+}
+
+impl SpinChecker for AllSpinChecker {
+    fn calc_spin_type(&self, control_block: &ControlBlock, field: &Field) -> TSpinType {
+        if control_block.block_type == BlockType::T {
+            return self.t_spin_checker.calc_t_spin_type(control_block, field);
+        }
+        if control_block.get_last_rotation_kick().is_some() && control_block.is_immobile(field) {
+            TSpinType::Other
+        }
+        else {
+            TSpinType::None
+        }
+    }
+}
+
+/// ルールで選べるスピン判定の種類.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SpinCheckerType {
+    TSpinOnly,
+    AllSpin,
+}
+
+impl SpinCheckerType {
+    /// 対応する判定を作る.
+    pub fn create(self) -> Box<dyn SpinChecker + Send> {
+        match self {
+            SpinCheckerType::TSpinOnly => Box::new(TSpinOnlyChecker::new()),
+            SpinCheckerType::AllSpin => Box::new(AllSpinChecker::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::block::{piece_set::PieceSet, rotation_system::RotationSystemType};
+    use crate::gameplay::field::FieldConfig;
+    use crate::utility::grid::Grid;
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    /// ブロックのあるマス以外を全て埋めたフィールドを作る.
+    fn surround(control_block: &ControlBlock) -> Field {
+        let mut field = Field::new(FieldConfig::standard());
+        let config = *field.get_config();
+        let mut shape = vec![vec![BlockType::Attacked; config.width]; config.total_height()];
+        let pos_y_upper = control_block.position.y + 1 - control_block.block.len() as i32;
+        for (y, line) in control_block.block.iter().enumerate() {
+            for (x, cell) in line.iter().enumerate() {
+                if *cell != BlockType::None {
+                    shape[(pos_y_upper + y as i32) as usize][(control_block.position.x + x as i32) as usize] = BlockType::None;
+                }
+            }
+        }
+        field.lock_block(&shape, &Grid::new(0, config.total_height() as i32 - 1), Instant::now());
+        field
+    }
+
+    #[test]
+    fn test_all_spin() {
+        let field = Field::new(FieldConfig::standard());
+        let mut control_block = ControlBlock::new(RotationSystemType::Srs.create(), Arc::new(PieceSet::builtin().remove(0)), 1);
+        control_block.apply_block(BlockType::L, field.get_config().spawn_position());
+        let all_spin = SpinCheckerType::AllSpin.create();
+        let t_spin_only = SpinCheckerType::TSpinOnly.create();
+        assert!(control_block.rotate(&field));
+        // 空いているフィールドでは動かせるのでスピンではない.
+        assert_eq!(all_spin.calc_spin_type(&control_block, &field), TSpinType::None);
+        // 動かせない位置に回転して入ったらスピン.
+        let surrounded = surround(&control_block);
+        assert!(control_block.is_immobile(&surrounded));
+        assert_eq!(all_spin.calc_spin_type(&control_block, &surrounded), TSpinType::Other);
+        assert_eq!(t_spin_only.calc_spin_type(&control_block, &surrounded), TSpinType::None);
+        // 最後の操作が回転でなければスピンではない.
+        control_block.apply_block(BlockType::L, field.get_config().spawn_position());
+        let surrounded = surround(&control_block);
+        assert_eq!(all_spin.calc_spin_type(&control_block, &surrounded), TSpinType::None);
+    }
+}
@@ -1,39 +1,44 @@
 //! Tスピンに関する処理を行う.
 
 use crate::gameplay::block::{
-    control_block::ControlBlock,
-    block_datas::{self, BlockType}
+    control_block::{ControlBlock, RotationKick},
+    block_datas::BlockType,
 };
+use crate::gameplay::field::Field;
 use crate::utility::grid::Grid;
-use crate::utility::vector_util;
-use std::collections::HashMap;
 
 /// Tスピンの種別.
-/// Tブロック周辺にブロックが充分埋まっていない場合、または直前の動作がスピンでないと判定された場合はNone.
-/// Tブロックの突部分の両隣にブロックがある場合、または特殊な移動を含むスピンが行われていた場合はFull
-/// それ以外はFalse
-#[derive(PartialEq, Copy, Clone)]
+/// 最後の操作が回転でない場合、またはTブロック周辺にブロックが充分埋まっていない場合はNone.
+/// Tブロックの突部分の両隣にブロックがある場合、または90度回転で横に1マス、縦に2マスずれる補正を使った場合はFull
+/// それ以外はMini
+/// Tブロック以外のブロックのスピンは、全てのブロックのスピンを認めるルールでのみOtherになる.
+#[derive(PartialEq, Copy, Clone, Debug)]
 pub enum TSpinType {
     None,
     Mini,
     Full,
+    Other,
 }
-/// Tブロックの方向.
-/// Tブロックの凸部分がどの方向を向いているかを返す.
-#[derive(PartialEq, Eq, Hash)]
-enum TBlockDirection {
-    N,
-    E,
-    W,
-    S,
-    None,
-}
+
+/// Tブロックの3x3の四隅.左上、右上、左下、右下の順.
+const CORNERS: [Grid; 4] = [
+    Grid::new(0, 0),
+    Grid::new(2, 0),
+    Grid::new(0, 2),
+    Grid::new(2, 2),
+];
+
+/// Tブロックの向きごとの判定表.突部分の逆側の位置と、突部分の隣の隅のインデックス.
+/// 上、右、下、左向きの順に調べる.
+const T_DIRECTIONS: [(Grid, [usize; 2]); 4] = [
+    (Grid::new(1, 2), [0, 1]),
+    (Grid::new(0, 1), [1, 3]),
+    (Grid::new(1, 0), [2, 3]),
+    (Grid::new(2, 1), [0, 2]),
+];
 
 /// Tスピンが行われているかを判定するための構造体.
-/// 
 pub struct TSpinChecker {
-    t_block_data: Vec<Vec<BlockType>>,
-    t_block_position: Grid,
 }
 
 impl TSpinChecker {
@@ -41,99 +46,167 @@ impl TSpinChecker {
     /// ゲーム中使い回すことを想定しているため、個別のパラメータ指定はここではしない.
     pub fn new() -> Self {
         TSpinChecker{
-            t_block_data: block_datas::block_shape(BlockType::T),
-            t_block_position: Grid::new(0, 0),
         }
     }
 
-    /// Tスピンが行われているかの判定に用いるデータをセットする.
-    /// ブロックが移動する直前に呼び出されることを想定している.
-    pub fn set_block_data(&mut self, control_block: &ControlBlock){
-        if control_block.block_type == BlockType::T {
-            // Tスピン判定用データ更新
-            vector_util::copy_vec_2d(&mut self.t_block_data, &control_block.block);
-            self.t_block_position.copy_data(&control_block.position);
+    /// Tスピン判定.
+    /// ブロックが拡大されていても、拡大前の形で判定する.フィールドは拡大後の1マスの左上で代表させる.
+    pub fn calc_t_spin_type(&self, control_block: &ControlBlock, field: &Field) -> TSpinType {
+        if control_block.block_type != BlockType::T {
+            return TSpinType::None;
         }
-    }
-
-    /// Tスピンが行われているかを判定する.
-    /// 事前にset_block_dataを呼び出されていることを想定している.
-    pub fn check_t_spinned(&self, control_block: &ControlBlock) -> bool {
-        if control_block.block_type == BlockType::T && self.t_block_data != *control_block.block {
-            true
+        // 最後に成功した操作が回転でなければTスピンではない.
+        let Some(kick) = control_block.get_last_rotation() else {
+            return TSpinType::None;
+        };
+        let unit_block = control_block.unit_block();
+        let Some(front_corners) = Self::search_front_corners(&unit_block) else {
+            // Tブロックじゃなさそう…
+            return TSpinType::None;
+        };
+        // 四隅がどれだけ埋まっているか数える.フィールドの外は埋まっているものとする.
+        let scale = control_block.get_scale() as i32;
+        let pos_y_upper = control_block.position.y + 1 - control_block.block.len() as i32;
+        let filled: Vec<bool> = CORNERS.iter().map(|corner| {
+            let check_pos = Grid::new(control_block.position.x + corner.x * scale, pos_y_upper + corner.y * scale);
+            !field.check_position_in_field(&check_pos) || field.get_grid_data(&check_pos) != BlockType::None
+        }).collect();
+        let corner_count = filled.iter().filter(|filled| **filled).count();
+        let front_count = front_corners.iter().filter(|index| filled[**index]).count();
+        if corner_count < 3 {
+            TSpinType::None
+        }
+        else if front_count == 2 || Self::is_special_kick(kick) {
+            TSpinType::Full
         }
         else {
-            // Tブロックでないor回転していない移動.
-            false
+            TSpinType::Mini
         }
     }
 
-    /// Tスピン判定
-    pub fn calc_t_spin_type(&self, control_block: &ControlBlock, field_data: &Vec<Vec<BlockType>>) -> TSpinType {
-        // 座標が特定の移動の仕方をしていた場合は、隣が空いていてもフル判定.
-        let srs_last_pattern_delta = Grid::new(1, 2);
-        let counter_srs_last_pattern_delta = Grid::new(-1, 2);
-        let pos_delta = control_block.position.clone() - &self.t_block_position;
-        let srs_last_pattern = pos_delta == srs_last_pattern_delta || pos_delta == counter_srs_last_pattern_delta;
+    /// 突部分の隣が空いていてもフル判定にする補正かどうか.
+    /// SRSの90度回転で横に1マス、縦に2マスずれる補正だけが該当する.180度回転の補正は番号が同じでも対象外.
+    fn is_special_kick(kick: &RotationKick) -> bool {
+        !kick.half_turn && kick.offset.x.abs() == 1 && kick.offset.y.abs() == 2
+    }
 
-        let t_direction = self.search_t_block_direction(&control_block.block);
-        if t_direction == TBlockDirection::None {
-            // Tブロックじゃなさそう…
-            return TSpinType::None;
+    /// Tブロックの突部分の隣の隅を調べる.
+    /// 3x3のTブロックでなければNone.
+    fn search_front_corners(t_block_data: &Vec<Vec<BlockType>>) -> Option<[usize; 2]> {
+        if t_block_data.len() != 3 || t_block_data.iter().any(|line| line.len() != 3) {
+            return None;
         }
-        // Tスピンのチェック.
-        let mut corner_count = 0;
-        let mut mini_count = 0;
-        let corners = [
-            Grid::new(0, 0),
-            Grid::new(2, 0),
-            Grid::new(0, 2),
-            Grid::new(2, 2),
-        ];
-        // 突部分の隣が空いてたらミニになるので、突部分の隣のインデックスを取っておく.
-        let mini_check_corners = match t_direction {
-            TBlockDirection::N => [0, 1],
-            TBlockDirection::E => [1, 3],
-            TBlockDirection::S => [3, 2],
-            TBlockDirection::W => [2, 0],
-            _ => [4, 4],
-        };
-        for (i, corner) in corners.iter().enumerate() {
-            if field_data[corner.y as usize][corner.x as usize] != BlockType::None {
-                corner_count += 1;
-                if i == mini_check_corners[0] || i == mini_check_corners[1] {
-                    mini_count += 1;
-                }
-            }
-        }
-        if corner_count < 3 {
-            return TSpinType::None;
+        // 突部分の逆側が空いていることで、方向を確認する.
+        T_DIRECTIONS.iter()
+            .find(|(back, _)| t_block_data[back.y as usize][back.x as usize] == BlockType::None)
+            .map(|(_, front_corners)| *front_corners)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::block::{piece_set::PieceSet, rotation_system::RotationSystemType};
+    use crate::gameplay::field::FieldConfig;
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    /// 指定したマスを埋めた10x20のフィールドを作る.座標はフィールドの下からの行と列.
+    fn make_field(filled: &[(i32, i32)]) -> Field {
+        let mut field = Field::new(FieldConfig::wide());
+        let bottom = field.get_config().total_height() as i32 - 1;
+        for (x, y) in filled {
+            field.lock_block(&vec![vec![BlockType::Attacked]], &Grid::new(*x, bottom - y), Instant::now());
         }
-        else{
-            if mini_count == 2 || srs_last_pattern {
-                return TSpinType::Full;
-            }
-            else{
-                return  TSpinType::Mini;
-            }
+        field
+    }
+
+    /// Tブロックを向きを変えてから、3x3の左下が(x, 下からy行目)になるように置く.
+    fn make_t_block(field: &Field, rotations: usize, x: i32, y: i32) -> ControlBlock {
+        let mut control_block = ControlBlock::new(RotationSystemType::Srs.create(), Arc::new(PieceSet::builtin().remove(0)), 1);
+        control_block.apply_block(BlockType::T, field.get_config().spawn_position());
+        for _ in 0..rotations {
+            assert!(control_block.rotate(field));
         }
+        control_block.position = Grid::new(x, field.get_config().total_height() as i32 - 1 - y);
+        assert!(!field.check_collision(&control_block.block, &control_block.position));
+        control_block
     }
 
-    
-    /// Tブロックの方向チェック.
-    /// Tブロック以外が入力されることは想定外のため、値の正しさは保障されない.
-    fn search_t_block_direction(&self, t_block_data: &Vec<Vec<BlockType>>) -> TBlockDirection {
-        // 突部分の逆側が空いていることで、方向を確認する.
-        let mut hips = HashMap::new();
-        hips.insert(TBlockDirection::N, [1, 2]);
-        hips.insert(TBlockDirection::E, [0, 1]);
-        hips.insert(TBlockDirection::W, [1, 0]);
-        hips.insert(TBlockDirection::S, [2, 1]);
-        for (direction, top) in hips {
-            if t_block_data[top[1]][top[0]] != BlockType::T {
-                return direction;
+    #[test]
+    fn test_t_spin_double() {
+        // 上向きのTを180度回転して、下向きで溝にはめる.
+        let field = make_field(&[(0, 0), (1, 0), (3, 0), (4, 0), (0, 1), (4, 1), (1, 2)]);
+        let mut control_block = make_t_block(&field, 0, 1, 0);
+        let checker = TSpinChecker::new();
+        assert!(control_block.rotate_180(&field));
+        assert_eq!(control_block.get_last_rotation_kick(), Some(0));
+        assert_eq!(checker.calc_t_spin_type(&control_block, &field), TSpinType::Full);
+        // 動けなかった移動では判定は変わらない.
+        assert!(!control_block.left(&field));
+        assert_eq!(checker.calc_t_spin_type(&control_block, &field), TSpinType::Full);
+    }
+
+    #[test]
+    fn test_t_spin_mini() {
+        // 突部分の隣が片方しか埋まっていなければミニ.
+        let field = make_field(&[(0, 0), (2, 0), (0, 2)]);
+        let mut control_block = make_t_block(&field, 3, 0, 0);
+        let checker = TSpinChecker::new();
+        assert!(control_block.rotate_180(&field));
+        assert_eq!(checker.calc_t_spin_type(&control_block, &field), TSpinType::Mini);
+    }
+
+    #[test]
+    fn test_t_spin_with_special_kick() {
+        // 横に1マス、縦に2マスずれる補正で入った場合は、突部分の隣が空いていてもフル.
+        let mut filled = vec![(3, 4)];
+        for y in 0..3 {
+            for x in 0..10 {
+                // Tブロックが入るマスと、突部分の隣の隅は空けておく.
+                let empty = (x == 3 && y <= 2) || (x == 4 && y <= 1);
+                if !empty {
+                    filled.push((x, y));
+                }
             }
         }
-        TBlockDirection::None
+        let field = make_field(&filled);
+        let mut control_block = make_t_block(&field, 0, 3, 2);
+        let checker = TSpinChecker::new();
+        assert!(control_block.rotate(&field));
+        assert_eq!(control_block.get_last_rotation_kick(), Some(4));
+        assert_eq!(control_block.position, Grid::new(2, field.get_config().total_height() as i32 - 1));
+        assert_eq!(checker.calc_t_spin_type(&control_block, &field), TSpinType::Full);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_half_turn_kick_is_not_special() {
+        // 右向きから180度回転して、4番の補正(上に2マス)で左向きになる.突部分の隣が片方しか埋まっていなければミニのまま.
+        let field = make_field(&[(3, 1), (5, 2), (5, 4), (3, 4)]);
+        let mut control_block = make_t_block(&field, 1, 3, 0);
+        let checker = TSpinChecker::new();
+        assert!(control_block.rotate_180(&field));
+        assert_eq!(control_block.get_last_rotation_kick(), Some(4));
+        assert_eq!(control_block.position, Grid::new(3, field.get_config().total_height() as i32 - 3));
+        assert_eq!(checker.calc_t_spin_type(&control_block, &field), TSpinType::Mini);
+    }
+
+    #[test]
+    fn test_not_t_spin() {
+        let field = make_field(&[(0, 0), (1, 0), (3, 0), (4, 0), (0, 1), (4, 1), (1, 2)]);
+        let checker = TSpinChecker::new();
+        // 回転せずに置いただけならTスピンではない.
+        let control_block = make_t_block(&field, 0, 1, 0);
+        assert_eq!(checker.calc_t_spin_type(&control_block, &field), TSpinType::None);
+        // 回転した後に移動したらTスピンではない.
+        let mut control_block = make_t_block(&field, 0, 5, 3);
+        assert!(control_block.rotate(&field));
+        assert!(control_block.down(&field));
+        assert_eq!(checker.calc_t_spin_type(&control_block, &field), TSpinType::None);
+        // 隅が2つ以下ならTスピンではない.
+        let empty_field = make_field(&[]);
+        let mut control_block = make_t_block(&empty_field, 0, 3, 0);
+        assert!(control_block.rotate_180(&empty_field));
+        assert_eq!(checker.calc_t_spin_type(&control_block, &empty_field), TSpinType::None);
+    }
+}
@@ -0,0 +1,129 @@
+//! 複数人で遊ぶ場合の攻撃先の選び方.
+use rand::{Rng, rngs::StdRng};
+
+/// 攻撃先の選び方.プレイ中にキーで切り替えられる.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TargetingType {
+    /// 全員に攻撃する.
+    All,
+    /// ランダムに1人選ぶ.攻撃するまでは同じ相手を狙い続ける.
+    Random,
+    /// 自分を攻撃してきた相手全員.いなければランダム.
+    Attackers,
+    /// バッジを一番多く持っている相手.
+    Badges,
+    /// 一番積み上がっている(ゲームオーバーに近い)相手.
+    KO,
+}
+
+/// 攻撃先を選ぶための、相手の情報.
+pub struct Opponent {
+    pub index: usize,
+    pub badges: u32,
+    pub stack_height: usize,
+    pub is_attacking_me: bool,
+}
+
+impl TargetingType {
+    /// 切り替えキーを押したときの次の選び方を返す.
+    pub fn next(self) -> Self {
+        match self {
+            TargetingType::All => TargetingType::Random,
+            TargetingType::Random => TargetingType::Attackers,
+            TargetingType::Attackers => TargetingType::Badges,
+            TargetingType::Badges => TargetingType::KO,
+            TargetingType::KO => TargetingType::All,
+        }
+    }
+
+    /// 攻撃先を選び、相手のインデックスを返す.
+    /// opponentsにはゲームオーバーになっていない相手だけを渡す.
+    /// current_targetsは今狙っている相手で、ランダムの場合はまだ狙えるならそのまま使う.
+    pub fn select_targets(self, opponents: &[Opponent], current_targets: &[usize], rng: &mut StdRng) -> Vec<usize> {
+        if opponents.is_empty() {
+            return vec![];
+        }
+        match self {
+            TargetingType::All => opponents.iter().map(|opponent| opponent.index).collect(),
+            TargetingType::Random => {
+                if let [current] = current_targets {
+                    if opponents.iter().any(|opponent| opponent.index == *current) {
+                        return vec![*current];
+                    }
+                }
+                vec![opponents[rng.random_range(0..opponents.len())].index]
+            },
+            TargetingType::Attackers => {
+                let attackers: Vec<usize> = opponents.iter()
+                    .filter(|opponent| opponent.is_attacking_me)
+                    .map(|opponent| opponent.index)
+                    .collect();
+                if attackers.is_empty() {
+                    TargetingType::Random.select_targets(opponents, current_targets, rng)
+                }
+                else {
+                    attackers
+                }
+            },
+            // 同じ数の相手がいる場合は、先に見つかった方を狙う.
+            TargetingType::Badges => {
+                let mut target = &opponents[0];
+                for opponent in opponents {
+                    if opponent.badges > target.badges {
+                        target = opponent;
+                    }
+                }
+                vec![target.index]
+            },
+            TargetingType::KO => {
+                let mut target = &opponents[0];
+                for opponent in opponents {
+                    if opponent.stack_height > target.stack_height {
+                        target = opponent;
+                    }
+                }
+                vec![target.index]
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn opponents() -> Vec<Opponent> {
+        vec![
+            Opponent { index: 1, badges: 0, stack_height: 5, is_attacking_me: false },
+            Opponent { index: 2, badges: 2, stack_height: 3, is_attacking_me: true },
+            Opponent { index: 3, badges: 1, stack_height: 8, is_attacking_me: true },
+        ]
+    }
+
+    #[test]
+    fn test_select_targets() {
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(TargetingType::All.select_targets(&opponents(), &[], &mut rng), vec![1, 2, 3]);
+        assert_eq!(TargetingType::Attackers.select_targets(&opponents(), &[], &mut rng), vec![2, 3]);
+        assert_eq!(TargetingType::Badges.select_targets(&opponents(), &[], &mut rng), vec![2]);
+        assert_eq!(TargetingType::KO.select_targets(&opponents(), &[], &mut rng), vec![3]);
+        assert!(TargetingType::KO.select_targets(&[], &[], &mut rng).is_empty());
+    }
+
+    #[test]
+    fn test_select_random_targets() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let targets = TargetingType::Random.select_targets(&opponents(), &[], &mut rng);
+        assert_eq!(targets.len(), 1);
+        // 狙える間は同じ相手を狙い続ける.
+        assert_eq!(TargetingType::Random.select_targets(&opponents(), &targets, &mut rng), targets);
+        let targets = TargetingType::Random.select_targets(&opponents(), &[4], &mut rng);
+        assert_ne!(targets, vec![4]);
+        // 攻撃してくる相手がいなければランダムに選ぶ.
+        let opponents: Vec<Opponent> = opponents().into_iter()
+            .map(|opponent| Opponent { is_attacking_me: false, ..opponent })
+            .collect();
+        assert_eq!(TargetingType::Attackers.select_targets(&opponents, &[], &mut rng).len(), 1);
+    }
+}
@@ -0,0 +1,119 @@
+//! 固定したブロック(スタック)をどう見せるかの決まり.
+//! フィールドの各マスが固定された時刻から、表示するかどうかを決める.
+use std::time::{Duration, Instant};
+
+/// フェードで固定したマスが消えるまでの時間.
+pub const FADING_MILLIS: u64 = 5000;
+/// 見えないモードで、ラインを消したときにスタックを見せる時間.
+pub const STACK_REVEAL_MILLIS: u64 = 1000;
+
+/// スタックの見せ方のトレイト.
+pub trait VisibilityPolicy {
+    /// lock_timeに固定されたマスを、nowの時点で表示するかどうか.
+    fn is_visible(&self, lock_time: Instant, now: Instant) -> bool;
+    /// ラインを消したときに、スタックを少しの間見せるかどうか.
+    fn reveals_on_clear(&self) -> bool {
+        false
+    }
+}
+
+/// 常に見える.通常の見せ方.
+pub struct AlwaysVisible {
+}
+
+impl AlwaysVisible {
+    /// 新規インスタンス作成.
+    pub fn new() -> Self {
+        AlwaysVisible {
+        }
+    }
+}
+
+impl VisibilityPolicy for AlwaysVisible {
+    fn is_visible(&self, _: Instant, _: Instant) -> bool {
+        true
+    }
+}
+
+/// 固定してから一定時間経つと見えなくなる.
+pub struct FadingVisibility {
+    fading_time: Duration,
+}
+
+impl FadingVisibility {
+    /// 新規インスタンス作成.
+    pub fn new(fading_time: Duration) -> Self {
+        FadingVisibility {
+            fading_time,
+        }
+    }
+}
+
+impl VisibilityPolicy for FadingVisibility {
+    fn is_visible(&self, lock_time: Instant, now: Instant) -> bool {
+        now.duration_since(lock_time) < self.fading_time
+    }
+}
+
+/// 固定した瞬間に見えなくなる.ラインを消したときだけ少しの間見える.
+pub struct InvisibleVisibility {
+}
+
+impl InvisibleVisibility {
+    /// 新規インスタンス作成.
+    pub fn new() -> Self {
+        InvisibleVisibility {
+        }
+    }
+}
+
+impl VisibilityPolicy for InvisibleVisibility {
+    fn is_visible(&self, _: Instant, _: Instant) -> bool {
+        false
+    }
+
+    fn reveals_on_clear(&self) -> bool {
+        true
+    }
+}
+
+/// タイトルで選べるスタックの見せ方.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum VisibilityType {
+    Normal,
+    Fading,
+    Invisible,
+}
+
+impl VisibilityType {
+    /// 対応する見せ方を作る.
+    pub fn create(self) -> Box<dyn VisibilityPolicy + Send> {
+        match self {
+            VisibilityType::Normal => Box::new(AlwaysVisible::new()),
+            VisibilityType::Fading => Box::new(FadingVisibility::new(Duration::from_millis(FADING_MILLIS))),
+            VisibilityType::Invisible => Box::new(InvisibleVisibility::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_visibility() {
+        let lock_time = Instant::now();
+        let later = |millis: u64| lock_time + Duration::from_millis(millis);
+        let normal = VisibilityType::Normal.create();
+        assert!(normal.is_visible(lock_time, later(FADING_MILLIS * 10)));
+        assert!(!normal.reveals_on_clear());
+
+        let fading = VisibilityType::Fading.create();
+        assert!(fading.is_visible(lock_time, later(FADING_MILLIS - 1)));
+        assert!(!fading.is_visible(lock_time, later(FADING_MILLIS)));
+
+        let invisible = VisibilityType::Invisible.create();
+        assert!(!invisible.is_visible(lock_time, lock_time));
+        assert!(invisible.reveals_on_clear());
+    }
+}
@@ -0,0 +1,57 @@
+//! 起動時のコマンドライン引数.
+
+use std::path::PathBuf;
+
+/// コマンドラインから指定できる設定.
+#[derive(Debug, Default, PartialEq)]
+pub struct LaunchOptions {
+    /// 毎回このシードでゲームを始める.指定が無ければゲームごとにランダム.
+    pub seed: Option<u64>,
+    /// 指定されていれば、タイトルの代わりにこのリプレイを再生する.
+    pub replay: Option<PathBuf>,
+}
+
+impl LaunchOptions {
+    /// 引数を解釈する.プログラム名は含めずに渡す.
+    /// 使えるのは`--seed <数値>`と`--replay <ファイル>`.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut options = LaunchOptions::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seed" => {
+                    let value = args.next().ok_or("--seed の後にシードを指定してください.")?;
+                    let seed = value.parse::<u64>()
+                        .map_err(|_| format!("シードは0以上の整数で指定してください: {}", value))?;
+                    options.seed = Some(seed);
+                },
+                "--replay" => {
+                    let value = args.next().ok_or("--replay の後にリプレイファイルを指定してください.")?;
+                    options.replay = Some(PathBuf::from(value));
+                },
+                _ => return Err(format!("不明な引数です: {}", arg)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_from_args() {
+        assert_eq!(LaunchOptions::from_args(args(&[])), Ok(LaunchOptions { seed: None, replay: None }));
+        assert_eq!(LaunchOptions::from_args(args(&["--seed", "1234"])), Ok(LaunchOptions { seed: Some(1234), replay: None }));
+        assert_eq!(LaunchOptions::from_args(args(&["--replay", "replays/a.txt"])), Ok(LaunchOptions { seed: None, replay: Some(PathBuf::from("replays/a.txt")) }));
+        assert!(LaunchOptions::from_args(args(&["--replay"])).is_err());
+        assert!(LaunchOptions::from_args(args(&["--seed"])).is_err());
+        assert!(LaunchOptions::from_args(args(&["--seed", "-1"])).is_err());
+        assert!(LaunchOptions::from_args(args(&["--level"])).is_err());
+    }
+}
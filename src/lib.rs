@@ -2,11 +2,14 @@ mod console_key_input;
 mod gameplay;
 mod console_renderer;
 mod console_renderer_sender;
+mod launch_options;
 mod utility;
 
+pub use crate::launch_options::LaunchOptions;
+
 use crate::{
     console_key_input::ConsoleKeyInput,
-    gameplay::game_manager::GameManager,
+    gameplay::{block::piece_set, game_clock, game_manager::GameManager, replay::Replay},
     console_renderer::render_manager::RenderManager,
     console_renderer_sender::game_sender::GameSender,
 };
@@ -14,8 +17,11 @@ use std::{thread, time::{Instant, Duration}, sync::Mutex};
 use std::sync::{Arc};
 use once_cell::sync::Lazy;
 
+/// 描画のフレームレート.ゲームの更新は[game_clock::TICKS_PER_SECOND]の間隔で別に進める.
 const FPS: u64 = 20;
 const FRAME_TIME_MILLIS: u64 = 1000 / FPS;
+/// 1フレームで進める更新の上限.処理が追いつかないときに、遅れを取り戻そうとして更新が溜まり続けないようにする.
+const MAX_TICKS_PER_FRAME: u32 = 10;
 
 pub static GAME_MANAGER: Lazy<Mutex<GameManager>> = Lazy::new(|| {
     Mutex::new(GameManager::new(Box::new(GameSender::new())
@@ -30,10 +36,11 @@ pub static RENDER_MANAGER: Lazy<Mutex<RenderManager>> = Lazy::new(|| {
     Mutex::new(RenderManager::new())
 });
 
-/// ゲームロジックの更新.
-fn update() -> bool{
+/// ゲームロジックの更新.1ティック分進める.
+/// キー入力はフレームの最初の更新でだけ読む.
+fn update(read_input: bool) -> bool{
     // ゲーム状態に応じた更新.
-    if !GAME_MANAGER.lock().unwrap().update() {
+    if !GAME_MANAGER.lock().unwrap().update(read_input) {
         // ゲーム終了処理.
         return false;
     }
@@ -47,16 +54,44 @@ fn render() {
 }
 
 /// メインループ.
-pub fn main_loop() {
+pub fn main_loop(options: LaunchOptions) {
+    // ブロック定義はゲームを作る前に読み込み、不正な定義があれば起動しない.
+    let piece_sets = match piece_set::load_piece_sets() {
+        Ok(piece_sets) => piece_sets,
+        Err(message) => {
+            eprintln!("ブロック定義の読み込みに失敗しました: {}", message);
+            std::process::exit(1);
+        }
+    };
+    GAME_MANAGER.lock().unwrap().set_piece_sets(piece_sets);
+    GAME_MANAGER.lock().unwrap().set_fixed_seed(options.seed);
+    // リプレイが指定されていれば、読み込んでから再生を始める.
+    if let Some(path) = &options.replay {
+        let result = Replay::load(path).and_then(|replay| GAME_MANAGER.lock().unwrap().start_replay(replay));
+        if let Err(message) = result {
+            eprintln!("リプレイを再生できません: {}", message);
+            return;
+        }
+    }
+    // 更新は描画とは別に、経過した時間の分だけ決まった間隔で進める.
+    let mut last_frame = Instant::now();
+    let mut lag = Duration::ZERO;
     loop {
-        let last_update = Instant::now();
-        if !update() {
-            break;
+        let frame_start = Instant::now();
+        lag = (lag + frame_start.duration_since(last_frame)).min(game_clock::TICK_DURATION * MAX_TICKS_PER_FRAME);
+        last_frame = frame_start;
+        let mut read_input = true;
+        while lag >= game_clock::TICK_DURATION {
+            if !update(read_input) {
+                return;
+            }
+            read_input = false;
+            lag -= game_clock::TICK_DURATION;
         }
         render();
         let now = Instant::now();
-        if Duration::from_millis(FRAME_TIME_MILLIS) > now.duration_since(last_update) {
-            let wait_time = Duration::from_millis(FRAME_TIME_MILLIS) - now.duration_since(last_update);
+        if Duration::from_millis(FRAME_TIME_MILLIS) > now.duration_since(frame_start) {
+            let wait_time = Duration::from_millis(FRAME_TIME_MILLIS) - now.duration_since(frame_start);
             thread::sleep(wait_time);
         }
     }
@@ -1,7 +1,14 @@
 extern crate console_fall_puzzle;
 
-use console_fall_puzzle::main_loop;
+use console_fall_puzzle::{main_loop, LaunchOptions};
 
 fn main() {
-    main_loop();
+    let options = match LaunchOptions::from_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+    main_loop(options);
 }
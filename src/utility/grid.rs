@@ -10,7 +10,7 @@ pub struct Grid {
 }
 impl Grid {
     /// 新規インスタンス作成
-    pub fn new(x: i32, y: i32) -> Self {
+    pub const fn new(x: i32, y: i32) -> Self {
         Grid { x, y }
     }
 
@@ -12,7 +12,7 @@ pub fn rotate_vec_90_clockwise<T: Copy>(matrix: &Vec<Vec<T>>) -> Vec<Vec<T>> {
     if rows != cols {
         return matrix.clone();
     }
-    let mut rotated = vec![vec![matrix[0][0].clone(); rows]; cols];
+    let mut rotated = vec![vec![matrix[0][0]; rows]; cols];
     for r in 0..rows {
         for c in 0..cols {
             rotated[c][rows - 1 - r] = matrix[r][c];
@@ -28,7 +28,7 @@ pub fn rotate_vec_90_counterclockwise<T: Copy>(matrix: &Vec<Vec<T>>) -> Vec<Vec<
     if rows != cols {
         return matrix.clone();
     }
-    let mut rotated = vec![vec![matrix[0][0].clone(); rows]; cols];
+    let mut rotated = vec![vec![matrix[0][0]; rows]; cols];
     for r in 0..rows {
         for c in 0..cols {
             rotated[cols - 1 - c][r] = matrix[r][c];
@@ -37,21 +37,6 @@ pub fn rotate_vec_90_counterclockwise<T: Copy>(matrix: &Vec<Vec<T>>) -> Vec<Vec<
     rotated
 }
 
-/// 二次元ベクトルをコピーする.ベクトルの大きさが違ったらコピー出来る分だけコピーしてしまう.
-pub fn copy_vec_2d<T: Copy>(target: &mut Vec<Vec<T>>, source: &Vec<Vec<T>>) {
-    for y in 0..source.len() {
-        if y >= target.len() {
-            break;
-        }
-        for x in 0..source[y].len() {
-            if x >= target[y].len() {
-                break;
-            }
-            target[y][x] = source[y][x];
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;